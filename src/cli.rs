@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use log::LevelFilter;
+
+/***
+Opts: Command-line options for fluxr.
+    Every dashboard is just a tasks.toml, so point --config at whichever one you want to run.
+ */
+#[derive(Parser, Debug)]
+#[command(name = "fluxr", version, about = "A terminal dashboard for periodically run commands")]
+pub struct Opts {
+    /// Path to the tasks/layout definition to load
+    #[arg(short, long, value_name = "FILE", default_value = "config/tasks.toml")]
+    pub config: PathBuf,
+
+    /// Path to write the log to
+    #[arg(short, long, value_name = "FILE", default_value = "log/flux.log")]
+    pub log: PathBuf,
+
+    /// Minimum level of log messages to write (off, error, warn, info, debug, trace)
+    #[arg(long, value_name = "LEVEL", default_value = "info")]
+    pub log_level: LevelFilter,
+
+    #[command(subcommand)]
+    pub mode: Option<Mode>,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Run the dashboard (the default)
+    Run,
+    /// Load the config and report whether it is valid, without starting the UI
+    Check,
+    /// Print the view tree described by the config
+    PrintLayout,
+}
+
+impl Opts {
+    pub fn mode(&self) -> Mode {
+        self.mode.unwrap_or(Mode::Run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_to_run_mode_and_checkout_paths() {
+        let opts = Opts::parse_from(vec!["fluxr"]);
+        assert_eq!(opts.mode(), Mode::Run);
+        assert_eq!(opts.config, PathBuf::from("config/tasks.toml"));
+        assert_eq!(opts.log, PathBuf::from("log/flux.log"));
+        assert_eq!(opts.log_level, LevelFilter::Info);
+    }
+
+    #[test]
+    fn parses_paths_level_and_subcommand() {
+        let opts = Opts::parse_from(vec!["fluxr", "--config", "/tmp/dash.toml", "-l", "/tmp/dash.log", "--log-level", "debug", "print-layout"]);
+        assert_eq!(opts.mode(), Mode::PrintLayout);
+        assert_eq!(opts.config, PathBuf::from("/tmp/dash.toml"));
+        assert_eq!(opts.log, PathBuf::from("/tmp/dash.log"));
+        assert_eq!(opts.log_level, LevelFilter::Debug);
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use log::{info, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::events::Event;
+
+// Editors tend to write a file in several steps. Wait for things to settle before reloading.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/***
+watch_config: Tell the UI to reload whenever the config file changes.
+    We watch the file's directory rather than the file itself, since many editors save by
+    replacing the file - which would silently end a watch on the original.
+ */
+pub fn watch_config(config_path: &Path, event_sender: Sender<Event>) -> notify::Result<()> {
+    let config_path = config_path.canonicalize()?;
+    let dir = config_path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = RecommendedWatcher::new(tx, notify::Config::default())?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    thread::Builder::new().name("config_watcher".to_string()).spawn(move || {
+        let _watcher = watcher; // Watching stops when this is dropped.
+        info!("Watching {} for changes", config_path.display());
+
+        while let Ok(event) = rx.recv() {
+            if !touches(&event, &config_path) { continue; }
+
+            // Swallow the rest of this burst of events
+            while rx.recv_timeout(SETTLE_TIME).is_ok() {}
+
+            info!("{} changed", config_path.display());
+            if event_sender.send(Event::ConfigChanged).is_err() { break; }
+        }
+    })?;
+
+    Ok(())
+}
+
+fn touches(event: &notify::Result<notify::Event>, config_path: &Path) -> bool {
+    match event {
+        Ok(event) => (event.kind.is_create() || event.kind.is_modify()) &&
+            event.paths.iter().any(|p| p == config_path),
+        Err(err) => {
+            warn!("Error watching config: {}", err);
+            false
+        }
+    }
+}
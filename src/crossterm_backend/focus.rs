@@ -0,0 +1,120 @@
+use crate::widgets::Region;
+
+/***
+Direction: Which way to move the focus, from the panel which has it.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// The panel after the one with id `from`, in the order they're laid out - back round to the first after the last.
+/// The first, if none has the focus.
+pub fn next<'a>(regions: &'a [Region], from: Option<&str>) -> Option<&'a Region> {
+    match regions.iter().position(|r| Some(r.id.as_str()) == from) {
+        Some(i) => regions.get((i + 1) % regions.len()),
+        None => regions.first()
+    }
+}
+
+/// The panel before the one with id `from`, in the order they're laid out - back round to the last before the first.
+pub fn previous<'a>(regions: &'a [Region], from: Option<&str>) -> Option<&'a Region> {
+    match regions.iter().position(|r| Some(r.id.as_str()) == from) {
+        Some(i) => regions.get((i + regions.len() - 1) % regions.len()),
+        None => regions.last()
+    }
+}
+
+/// The nearest panel in `direction` from the one with id `from`. Of those, ones alongside it win over
+/// ones off at an angle.
+pub fn neighbour<'a>(regions: &'a [Region], from: &str, direction: Direction) -> Option<&'a Region> {
+    let current = regions.iter().find(|r| r.id == from)?;
+    type Span = fn(&Region) -> (usize, usize);
+    let (across, along): (Span, Span) = match direction {
+        Direction::Left | Direction::Right => (horizontal, vertical),
+        Direction::Up | Direction::Down => (vertical, horizontal)
+    };
+
+    let (start, end) = across(current);
+    regions.iter().
+        filter_map(|r| {
+            let (r_start, r_end) = across(r);
+            let distance = match direction {
+                Direction::Left | Direction::Up if r_end <= start => start - r_end,
+                Direction::Right | Direction::Down if r_start >= end => r_start - end,
+                _ => return None // Not that way at all
+            };
+            Some((gap(along(current), along(r)), distance, r))
+        }).
+        min_by_key(|(misalignment, distance, _)| (*misalignment, *distance)).
+        map(|(_, _, r)| r)
+}
+
+fn horizontal(r: &Region) -> (usize, usize) { (r.origin.0, r.origin.0 + r.size.0) }
+
+fn vertical(r: &Region) -> (usize, usize) { (r.origin.1, r.origin.1 + r.size.1) }
+
+/// How far apart two spans are - 0 if they overlap.
+fn gap(a: (usize, usize), b: (usize, usize)) -> usize {
+    b.0.saturating_sub(a.1).max(a.0.saturating_sub(b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(id: &str, origin: (usize, usize), size: (usize, usize)) -> Region {
+        Region { id: id.to_string(), origin, size }
+    }
+
+    // +------+----+
+    // | left | tr |
+    // |      +----+
+    // |      | br |
+    // +------+----+
+    // |  bottom   |
+    // +-----------+
+    fn dashboard() -> Vec<Region> {
+        vec![
+            region("left", (0, 0), (10, 6)),
+            region("tr", (10, 0), (5, 3)),
+            region("br", (10, 3), (5, 3)),
+            region("bottom", (0, 6), (15, 2)),
+        ]
+    }
+
+    fn id(region: Option<&Region>) -> Option<&str> {
+        region.map(|r| r.id.as_str())
+    }
+
+    #[test]
+    fn tabbing_goes_round_in_layout_order() {
+        let regions = dashboard();
+        assert_eq!(id(next(&regions, Some("left"))), Some("tr"));
+        assert_eq!(id(next(&regions, Some("bottom"))), Some("left"));
+        assert_eq!(id(previous(&regions, Some("left"))), Some("bottom"));
+        assert_eq!(id(next(&regions, None)), Some("left"));
+        assert_eq!(id(next(&[], None)), None);
+    }
+
+    #[test]
+    fn moves_to_the_nearest_panel_that_way() {
+        let regions = dashboard();
+        assert_eq!(id(neighbour(&regions, "left", Direction::Right)), Some("tr"));
+        assert_eq!(id(neighbour(&regions, "br", Direction::Left)), Some("left"));
+        assert_eq!(id(neighbour(&regions, "tr", Direction::Down)), Some("br"));
+        assert_eq!(id(neighbour(&regions, "br", Direction::Down)), Some("bottom"));
+        assert_eq!(id(neighbour(&regions, "bottom", Direction::Up)), Some("left"));
+    }
+
+    #[test]
+    fn stays_put_at_the_edge() {
+        let regions = dashboard();
+        assert_eq!(id(neighbour(&regions, "left", Direction::Left)), None);
+        assert_eq!(id(neighbour(&regions, "tr", Direction::Up)), None);
+        assert_eq!(id(neighbour(&regions, "missing", Direction::Up)), None);
+    }
+}
@@ -1,78 +1,67 @@
 use log::info;
-use std::sync::mpsc::{Sender, SendError};
-use std::collections::HashMap;
-use crossterm::event::{read, Event, KeyModifiers, KeyCode, KeyEvent};
+use std::sync::mpsc::Sender;
+use crossterm::event::{read, KeyModifiers, KeyCode, KeyEvent};
+use crossterm::event::Event as TermEvent;
+use crate::events::{ConsoleEdit, Event};
 
-pub fn wait_for_keypress(command_sender: Sender<HashMap<String, String>>) -> Result<(), SendError<HashMap<String, String>>> {
+pub fn wait_for_keypress(event_sender: Sender<Event>) {
     loop {
-        let console = "console".to_string();
-        match read().unwrap() {
-            Event::Key(event) => {
-                match event {
-                    // CTRL_C
-                    KeyEvent {
-                        code: KeyCode::Char('c'),
-                        modifiers: KeyModifiers::CONTROL
-                    } => {
-                        // exit...
-                        info!("Shutting down...");
-                        let mut h = HashMap::new();
-                        h.insert("system".to_string(), "\\u001bQ".to_string());
-                        command_sender.send(h)?;
-                        break; // exit the loop and stop accepting input
-                    },
-                    // CTRL_U
-                    KeyEvent {
-                        code: KeyCode::Char('u'),
-                        modifiers: KeyModifiers::CONTROL
-                    } => {
-                        info!("Clear buffer");
-                        let mut h = HashMap::new();
-                        h.insert(console, "\\u001bU".to_string());
-                        command_sender.send(h)?;
-                    },
-                    // ENTER
-                    KeyEvent {
-                        code: KeyCode::Enter,
-                        modifiers: KeyModifiers::NONE
-                    } => {
-                        let mut h = HashMap::new();
-                        h.insert(console, "\n".to_string());
-                        command_sender.send(h)?;
-                    },
-                    // BACKSPACE
-                    KeyEvent {
-                        code: KeyCode::Backspace,
-                        modifiers: KeyModifiers::NONE
-                    } => {
-                        let mut h = HashMap::new();
-                        h.insert(console, "\\h".to_string());
-                        command_sender.send(h)?;
-                    },
-                    // General key press
-                    KeyEvent {
-                        code: KeyCode::Char(c),
-                        modifiers: KeyModifiers::NONE
-                    } => {
-                        let mut h = HashMap::new();
-                        h.insert(console, c.to_string());
-                        command_sender.send(h)?;
-                    },
-                    KeyEvent {
-                        code: KeyCode::Char(c),
-                        modifiers: KeyModifiers::SHIFT
-                    } => {
-                        let mut h = HashMap::new();
-                        h.insert(console, c.to_string().to_uppercase());
-                        command_sender.send(h)?;
-                    },
-                    // I don't care about anything else
-                    _ => {}
-                }
+        let events = match read().unwrap() {
+            TermEvent::Key(event) => {
                 info!("Key Event: {:?}", event);
+                key_events(event)
             },
-            _ => {} // I don't care about these events.
+            TermEvent::Resize(w, h) => vec![Event::Resize(w, h)],
+            _ => vec![] // I don't care about these events.
+        };
+
+        for event in events {
+            let quitting = matches!(event, Event::Quit);
+
+            // If the UI has gone away, there's nobody left to listen.
+            if event_sender.send(event).is_err() || quitting {
+                info!("Shutting down...");
+                return; // exit the loop and stop accepting input
+            }
         }
     }
-    Ok(())
-}
\ No newline at end of file
+}
+
+fn key_events(event: KeyEvent) -> Vec<Event> {
+    match event {
+        // CTRL_C
+        KeyEvent {
+            code: KeyCode::Char('c'),
+            modifiers: KeyModifiers::CONTROL
+        } => vec![Event::Quit],
+        // CTRL_U
+        KeyEvent {
+            code: KeyCode::Char('u'),
+            modifiers: KeyModifiers::CONTROL
+        } => {
+            info!("Clear buffer");
+            vec![Event::ConsoleEdit(ConsoleEdit::Clear)]
+        },
+        // ENTER
+        KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE
+        } => vec![Event::ConsoleEdit(ConsoleEdit::Submit)],
+        // BACKSPACE
+        KeyEvent {
+            code: KeyCode::Backspace,
+            modifiers: KeyModifiers::NONE
+        } => vec![Event::ConsoleEdit(ConsoleEdit::Backspace)],
+        // General key press
+        KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE
+        } => vec![Event::ConsoleEdit(ConsoleEdit::Insert(c))],
+        KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::SHIFT
+        } => c.to_uppercase().map(|upper| Event::ConsoleEdit(ConsoleEdit::Insert(upper))).collect(),
+        // Anything else is up to the UI
+        _ => vec![Event::Key(event)]
+    }
+}
@@ -1,34 +1,51 @@
+mod focus;
 mod input;
+mod pages;
 
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::{stdout, Stdout, Write};
+use std::path::PathBuf;
 use std::rc::{Rc, Weak};
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::time::{Instant};
 
+use chrono::{DateTime, Local};
 use crossterm::cursor::{Hide, MoveTo, MoveToNextLine};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use crossterm::{QueueableCommand, Result};
 use crossterm::style::{Colorize, Print, PrintStyledContent};
 use crossterm::terminal::{Clear, ClearType};
-use log::{info, trace};
-use regex::{Match, Regex};
+use log::{info, trace, warn};
 
 use crate::TaskId;
-use crate::tasks::Layout;
-use crate::widgets::{Dim, LinearLayout, Orientation, TextView, View};
+use crate::events::{ConsoleEdit, Event};
+use crate::runner::{task_to_command, RunnerCommand};
+use crate::process::Stream;
+use crate::pty::WindowSize;
+use crate::run_result::{Exit, RunResult};
+use crate::schedule::parse_duration;
+use crate::tasks::{self, Config, Extent, Layout, OutputMode, Size, Task, TaskMode};
+use crate::widgets::{Align, Border, BorderStyle, Dim, Frame, Grid, LinearLayout, Orientation, Overflow, Scroll, Tabs, TextView, View};
 use std::thread;
+use crate::crossterm_backend::focus::Direction;
 use crate::crossterm_backend::input::wait_for_keypress;
+use crate::crossterm_backend::pages::Pager;
 
 type WindowMap = HashMap<TaskId, Weak<RefCell<TextView>>>;
 type RcView = Rc<RefCell<dyn View>>;
 
 pub struct CrossTermUiContext {
     windows: WindowMap,
+    pagers: Vec<Pager>,
     top_view: RcView,
-    command_receiver: Receiver<HashMap<TaskId, String>>,
-    command_sender: Sender<HashMap<String, String>>,
-    task_sender: Sender<String>,
+    tasks: Vec<Task>,
+    config_path: PathBuf,
+    event_receiver: Receiver<Event>,
+    event_sender: Sender<Event>,
+    task_sender: Sender<RunnerCommand>,
+    terminal_sizes: HashMap<TaskId, WindowSize>,
+    focused: Option<TaskId>,
     fps_tracker: FpsTracker,
     console_text: String,
     stdout: Stdout,
@@ -36,18 +53,27 @@ pub struct CrossTermUiContext {
 }
 
 impl CrossTermUiContext {
-    pub fn new(layout: Layout, command_receiver: Receiver<HashMap<TaskId, String>>, command_sender: Sender<HashMap<String, String>>, task_sender: Sender<String>) -> CrossTermUiContext {
+    pub fn new(config: Config, config_path: PathBuf, event_receiver: Receiver<Event>, event_sender: Sender<Event>, task_sender: Sender<RunnerCommand>) -> CrossTermUiContext {
         let mut windows = WindowMap::new();
-        let top_view = construct_layout(&layout, &mut windows);
+        let mut pagers = vec![];
+        let top_view = construct_layout(&config.layout, &mut windows, &mut pagers);
+        prepare_views(&config.tasks, &config.layout, &windows);
+        let focused = config.layout.main_task_id();
+        focus(&windows, focused.as_deref());
         let fps_tracker = FpsTracker { updates: 0.0, elapsed: 0 };
         let console_text = String::new();
 
         CrossTermUiContext {
             windows,
+            pagers,
             top_view,
-            command_receiver,
-            command_sender,
+            tasks: config.tasks,
+            config_path,
+            event_receiver,
+            event_sender,
             task_sender,
+            terminal_sizes: HashMap::new(),
+            focused,
             fps_tracker,
             console_text,
             stdout: stdout(),
@@ -55,7 +81,7 @@ impl CrossTermUiContext {
         }
     }
 
-    pub fn run_ui_loop(&mut self) -> (){
+    pub fn run_ui_loop(&mut self) {
         crossterm::terminal::enable_raw_mode().unwrap();
         self.stdout.
             queue(Hide).unwrap().
@@ -64,16 +90,19 @@ impl CrossTermUiContext {
 
         self.stdout.flush().unwrap();
 
-        let command_sender = self.command_sender.clone();
-        thread::spawn( move || { wait_for_keypress(command_sender) });
+        let event_sender = self.event_sender.clone();
+        thread::spawn( move || { wait_for_keypress(event_sender) });
+
+        // Size pty tasks' terminals before anything's happened, rather than when the first event arrives
+        self.reinflate_ui().unwrap_or_else(|_| trace!("Failed to reinflate ui!"));
 
         let mut last_log = Instant::now();
         while self.running {
             let start = Instant::now();
 
             if self.wait_for_updates() {
-                self.reinflate_ui().unwrap_or({trace!("Failed to reinflate ui!")});
-                self.draw_ui().unwrap_or({trace!("Failed to draw ui!")});
+                self.reinflate_ui().unwrap_or_else(|_| trace!("Failed to reinflate ui!"));
+                self.draw_ui().unwrap_or_else(|_| trace!("Failed to draw ui!"));
             }
 
             self.fps_tracker.elapsed += start.elapsed().as_millis();
@@ -91,16 +120,25 @@ impl CrossTermUiContext {
             queue(crossterm::terminal::LeaveAlternateScreen).unwrap();
         self.stdout.flush().unwrap();
         crossterm::terminal::disable_raw_mode().unwrap();
-        return;
     }
 
     fn wait_for_updates(&mut self) -> bool {
-        match self.command_receiver.recv() {
-            Ok(cmd_text) => {
-                self.handle_commands(&cmd_text);
+        // Wait for the next event, or for the next page to turn by itself - whichever comes first
+        let event = match self.pagers.iter().filter_map(Pager::due).min() {
+            Some(due) => self.event_receiver.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => self.event_receiver.recv().map_err(RecvTimeoutError::from)
+        };
+
+        match event {
+            Ok(event) => {
+                self.handle_event(event);
+                true
+            },
+            Err(RecvTimeoutError::Timeout) => {
+                self.rotate_pages();
                 true
             },
-            Err(_) => { false }
+            Err(RecvTimeoutError::Disconnected) => { false }
         }
     }
 
@@ -116,6 +154,9 @@ impl CrossTermUiContext {
                 queue(MoveToNextLine(1))?;
         }
 
+        // What was drawn last time can be taller - Ex: on another page of some tabs
+        self.stdout.queue(Clear(ClearType::FromCursorDown))?;
+
         self.draw_console()?;
 
         self.stdout.flush()?;
@@ -140,32 +181,163 @@ impl CrossTermUiContext {
         Ok(())
     }
 
-    pub fn handle_commands(&mut self, commands: &HashMap<String, String>) {
-        for (task_id, content) in commands {
-            match task_id.as_str() {
-                "system" => {
-                    match content.as_str() {
-                        "\\u001bQ" => self.running = false, // Shutting down
-                        _ => {} // No matching command
-                    }
-                },
-                "console" => {
-                    match content.as_str() {
-                        "\n" => self.execute_console_cmd(),
-                        "\\h" => if self.console_text.len() > 0 { self.console_text = self.console_text[0..self.console_text.len() - 1].to_string() },
-                        "\\u001bU" => self.console_text = String::new(),
-                        _ => self.console_text += content
-                    }
-                },
-                _ => match self.windows.get(task_id) {
-                    Some(text_view) => {
-                        self.fps_tracker.updates += 1.0;
-                        match text_view.upgrade() {
-                            None => {},
-                            Some(tv) => tv.borrow_mut().update_content(content.clone())
-                        }
-                    },
-                    None => {}
+    pub fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::Quit => self.running = false, // Shutting down
+            Event::ConfigChanged => self.reload_config(),
+            Event::Resize(w, h) => info!("Resized to {}x{}", w, h), // We reinflate after every event anyway
+            Event::Key(key) => self.handle_key(key),
+            Event::ConsoleEdit(edit) => match edit {
+                // With nothing typed, Enter re-runs the focused panel instead
+                ConsoleEdit::Submit if self.console_text.is_empty() => self.rerun_focused(),
+                ConsoleEdit::Submit => self.execute_console_cmd(),
+                ConsoleEdit::Backspace => { self.console_text.pop(); },
+                ConsoleEdit::Clear => self.console_text = String::new(),
+                ConsoleEdit::Insert(c) => self.console_text.push(c)
+            },
+            Event::TaskOutput(result) => self.show_result(result),
+            Event::TaskLine(id, stream, line) => self.show_line(&id, stream, &line),
+            Event::StreamEnded { id, exit, restart_in } => {
+                let failure = format!("{} - restarting in {}s", exit, restart_in.as_secs());
+                if let Some(tv) = self.windows.get(&id).and_then(|w| w.upgrade()) {
+                    let mut tv = tv.borrow_mut();
+                    tv.show_failure(Some(failure));
+                    tv.show_footer(Some(format!("{} · {}", Local::now().format("%H:%M:%S"), exit)));
+                }
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let regions = self.top_view.borrow().regions();
+        // Plain letters and numbers go to the console - they could be the start of a task's id
+        let alt = key.modifiers == KeyModifiers::ALT;
+        let scroll = match key.code {
+            KeyCode::Char('h') if alt => return self.move_focus(Direction::Left),
+            KeyCode::Char('j') if alt => return self.move_focus(Direction::Down),
+            KeyCode::Char('k') if alt => return self.move_focus(Direction::Up),
+            KeyCode::Char('l') if alt => return self.move_focus(Direction::Right),
+            KeyCode::Char('[') if alt => return self.turn_page(Tabs::previous),
+            KeyCode::Char(']') if alt => return self.turn_page(Tabs::next),
+            KeyCode::Char(c @ '1'..='9') if alt => return self.turn_page(|tabs| tabs.select(c as usize - '1' as usize)),
+            KeyCode::Tab => return self.set_focus(focus::next(&regions, self.focused.as_deref()).map(|r| r.id.clone())),
+            KeyCode::BackTab => return self.set_focus(focus::previous(&regions, self.focused.as_deref()).map(|r| r.id.clone())),
+            KeyCode::Up => Scroll::Up(1),
+            KeyCode::Down => Scroll::Down(1),
+            KeyCode::PageUp => Scroll::PageUp,
+            KeyCode::PageDown => Scroll::PageDown,
+            KeyCode::Home => Scroll::Top,
+            KeyCode::End => Scroll::Bottom,
+            _ => return trace!("Unbound key {:?}", key)
+        };
+
+        if let Some(tv) = self.focused.as_ref().and_then(|id| self.windows.get(id)).and_then(|w| w.upgrade()) {
+            tv.borrow_mut().scroll(scroll);
+        }
+    }
+
+    /// Move the focus to the nearest panel in `direction`, if there is one - it stays put at the edge of the screen.
+    fn move_focus(&mut self, direction: Direction) {
+        let regions = self.top_view.borrow().regions();
+        let neighbour = self.focused.as_deref().and_then(|id| focus::neighbour(&regions, id, direction));
+        if let Some(region) = neighbour {
+            self.set_focus(Some(region.id.clone()));
+        }
+    }
+
+    /// Turn the page of the tabs the focus is in - the innermost, if they're in one another - or if it's in none,
+    /// the outermost.
+    fn turn_page(&mut self, turn: impl FnOnce(&mut Tabs)) {
+        let focused = self.focused.clone();
+        let in_focus = |pager: &Pager| pager.tabs().upgrade().is_some_and(|tabs| tabs.borrow().regions().iter().any(|r| Some(&r.id) == focused.as_ref()));
+
+        // Tabs in tabs are built first, so they come before the tabs they're in
+        if let Some(i) = self.pagers.iter().position(in_focus).or(self.pagers.len().checked_sub(1)) {
+            self.pagers[i].turn(turn);
+            self.follow_page(i);
+        }
+    }
+
+    fn rotate_pages(&mut self) {
+        let now = Instant::now();
+        for i in 0..self.pagers.len() {
+            if self.pagers[i].due().is_some_and(|due| due <= now) {
+                self.pagers[i].turn(Tabs::next);
+                self.follow_page(i);
+            }
+        }
+    }
+
+    /// The focus can't stay on a page which has been turned away from - move it to the page turned to.
+    fn follow_page(&mut self, pager: usize) {
+        // Until it's inflated, nothing on the page turned to shows up. The whole screen is before it's drawn - until
+        // then, the space the tabs already have will do.
+        let tabs = self.pagers[pager].tabs().upgrade();
+        if let Some(tabs) = &tabs {
+            let mut tabs = tabs.borrow_mut();
+            let size = (tabs.width(), tabs.height());
+            tabs.inflate(&size);
+        }
+
+        let regions = self.top_view.borrow().regions();
+        if regions.iter().any(|r| Some(&r.id) == self.focused.as_ref()) { return; }
+
+        let page = tabs.map(|tabs| tabs.borrow().regions()).unwrap_or_default();
+        let focused = page.first().or_else(|| focus::next(&regions, None)).map(|r| r.id.clone());
+        self.set_focus(focused);
+    }
+
+    fn set_focus(&mut self, focused: Option<TaskId>) {
+        trace!("Focus moved to {:?}", focused);
+        focus(&self.windows, focused.as_deref());
+        self.focused = focused;
+    }
+
+    fn rerun_focused(&mut self) {
+        if let Some(id) = &self.focused {
+            info!("Running {}", id);
+            self.task_sender.send(RunnerCommand::Run(id.clone())).unwrap_or(());
+        }
+    }
+
+    fn show_line(&mut self, id: &str, stream: Stream, line: &str) {
+        let task = match self.tasks.iter().find(|t| t.id == id) {
+            Some(task) => task,
+            None => return
+        };
+
+        let wanted = match task.output {
+            OutputMode::Stdout => stream == Stream::Stdout,
+            OutputMode::Stderr => stream == Stream::Stderr,
+            OutputMode::Interleaved | OutputMode::StderrOnFailure => true
+        };
+
+        if let Some(tv) = self.windows.get(id).and_then(|w| w.upgrade()).filter(|_| wanted) {
+            self.fps_tracker.updates += 1.0;
+            let mut tv = tv.borrow_mut();
+            tv.show_failure(None); // If it's talking, it's running again
+            tv.append_line(line, task.scrollback());
+        }
+    }
+
+    fn show_result(&mut self, result: RunResult) {
+        trace!("{} started at {:?} and {} after {:.2?}", result.id, result.started, result.exit, result.duration);
+        let task = self.tasks.iter().find(|t| t.id == result.id);
+        let mode = task.map(|t| t.output).unwrap_or_default();
+        let manual = task.is_some_and(Task::is_manual);
+
+        if let Some(text_view) = self.windows.get(&result.id) {
+            self.fps_tracker.updates += 1.0;
+            if let Some(tv) = text_view.upgrade() {
+                let mut tv = tv.borrow_mut();
+                tv.show_failure(if result.succeeded() { None } else { Some(result.exit.to_string()) });
+                tv.show_footer(Some(run_summary(&result)));
+                // A skipped task didn't run, so whatever it last showed still stands
+                if let Exit::Skipped(_) = result.exit { return; }
+
+                tv.update_content(result.output(mode).to_string());
+                if manual {
+                    tv.show_status(Some(format!("last run at {}", DateTime::<Local>::from(result.started).format("%H:%M:%S"))));
                 }
             }
         }
@@ -176,14 +348,120 @@ impl CrossTermUiContext {
         let dims = (w as usize, h as usize); // Max size of the window.
         info!("Terminal size: {}x{}", w, h);
         self.top_view.borrow_mut().inflate(&dims);
+        self.report_terminal_sizes();
         Ok(())
     }
 
+    /// Let the runner know when the view of a task running in a pty has changed size, so its terminal can follow.
+    fn report_terminal_sizes(&mut self) {
+        for task in self.tasks.iter().filter(|t| t.pty) {
+            let (cols, rows) = match self.windows.get(&task.id).and_then(|w| w.upgrade()) {
+                Some(tv) => tv.borrow().room(),
+                None => continue
+            };
+
+            let size = WindowSize { rows: rows.min(u16::MAX as usize) as u16, cols: cols.min(u16::MAX as usize) as u16 };
+            if self.terminal_sizes.get(&task.id) != Some(&size) {
+                info!("{}'s terminal is now {}x{}", task.id, size.cols, size.rows);
+                self.terminal_sizes.insert(task.id.clone(), size);
+                self.task_sender.send(RunnerCommand::Resize(task.id.clone(), size)).unwrap_or(());
+            }
+        }
+    }
+
     fn execute_console_cmd(&mut self) {
         info!("Running {}", self.console_text);
-        self.task_sender.send(self.console_text.clone()).unwrap();
+        self.task_sender.send(RunnerCommand::Run(self.console_text.clone())).unwrap();
         self.console_text = String::new();
     }
+
+    fn reload_config(&mut self) {
+        let config = match tasks::load_task_config(&self.config_path) {
+            Ok(config) => config,
+            Err(errors) => {
+                for err in errors { warn!("Not reloading {}: {}", self.config_path.display(), err); }
+                return;
+            }
+        };
+
+        info!("Reloading {}", self.config_path.display());
+        let mut windows = WindowMap::new();
+        let mut pagers = vec![];
+        let top_view = construct_layout(&config.layout, &mut windows, &mut pagers);
+        prepare_views(&config.tasks, &config.layout, &windows);
+
+        // Stay on the same pages, for tabs which are still there - or at least where they were
+        for (old, new) in self.pagers.iter().zip(pagers.iter_mut()) {
+            if let Some(old) = old.tabs().upgrade() {
+                new.turn(|tabs| tabs.select(old.borrow().current()));
+            }
+        }
+
+        // Carry over the last output of any task which the runner won't restart, rather than blanking it until its
+        // next run - which for a manual task could be never. Its name or description alone don't count.
+        let unchanged: Vec<_> = self.tasks.iter().map(task_to_command).collect();
+        for task in config.tasks.iter().filter(|t| unchanged.contains(&task_to_command(t))) {
+            let old_view = self.windows.get(&task.id).and_then(|w| w.upgrade());
+            let new_view = windows.get(&task.id).and_then(|w| w.upgrade());
+            if let (Some(old_view), Some(new_view)) = (old_view, new_view) {
+                new_view.borrow_mut().carry_over(&old_view.borrow());
+            }
+        }
+
+        // Keep the focus where it was, if that task's still on show
+        self.focused = self.focused.take().filter(|id| windows.contains_key(id)).or_else(|| config.layout.main_task_id());
+        focus(&windows, self.focused.as_deref());
+        self.windows = windows;
+        self.pagers = pagers;
+        self.top_view = top_view;
+        self.tasks = config.tasks.clone();
+        self.task_sender.send(RunnerCommand::Reload(config.tasks)).unwrap_or(());
+    }
+}
+
+/// Set views up to suit their tasks. They're titled with the task's name and description for a border to show.
+/// Manual tasks might never run, so say so rather than leaving a blank space,
+/// streaming tasks show their latest output unless their view says otherwise, and tasks in a pty get a terminal
+/// to draw on.
+fn prepare_views(tasks: &[Task], layout: &Layout, windows: &WindowMap) {
+    let views = layout.text_views();
+    for task in tasks {
+        if let Some(tv) = windows.get(&task.id).and_then(|w| w.upgrade()) {
+            let mut tv = tv.borrow_mut();
+            let follow = views.iter().find(|v| v.task_id.as_ref() == Some(&task.id)).and_then(|v| v.follow);
+            tv.show_title(Some(match task.description.is_empty() {
+                true => task.name.clone(),
+                false => format!("{} - {}", task.name, task.description)
+            }));
+            if task.is_manual() { tv.show_status(Some(never_run(task))); }
+            tv.follow(follow.unwrap_or(task.mode == TaskMode::Stream));
+            if task.pty { tv.emulate_terminal(); }
+        }
+    }
+}
+
+/// What a manual task's view says until it first runs - and what'll run it, if something will.
+fn never_run(task: &Task) -> String {
+    match task.after.is_empty() {
+        true => format!("never run - enter '{}' to run it", task.id),
+        false => {
+            let upstream: Vec<String> = task.after.iter().map(|id| format!("'{}'", id)).collect();
+            format!("not run yet - it runs after {}, or enter '{}' to run it", upstream.join(", "), task.id)
+        }
+    }
+}
+
+/// When a run started, how long it took and how it finished - for the footer of its view.
+fn run_summary(result: &RunResult) -> String {
+    let started = DateTime::<Local>::from(result.started).format("%H:%M:%S");
+    format!("{} · {:.1}s · {}", started, result.duration.as_secs_f64(), result.exit)
+}
+
+/// Move the focus to the view of the task `focused` - the one the scrolling keys move.
+fn focus(windows: &WindowMap, focused: Option<&str>) {
+    for (id, tv) in windows {
+        if let Some(tv) = tv.upgrade() { tv.borrow_mut().focus(Some(id.as_str()) == focused); }
+    }
 }
 
 /*
@@ -195,69 +473,304 @@ struct FpsTracker {
     elapsed: u128
 }
 
-pub fn find_vt100s(s: &str) -> Vec<Match> {
-    let vt100_regex = Regex::new(r"((\u001b\[|\u009b)[\u0030-\u003f]*[\u0020-\u002f]*[\u0040-\u007e])+").unwrap();
-    vt100_regex.find_iter(s).collect()
-}
-
 /***
 Layout Inflation code
 TODO: Move this somewhere more appropriate
  */
 
-pub fn construct_layout(layout: &Layout, windows: &mut WindowMap) -> RcView {
+pub fn construct_layout(layout: &Layout, windows: &mut WindowMap, pagers: &mut Vec<Pager>) -> RcView {
     info!("Building {}:{}", layout.kind, layout.task_id.clone().unwrap_or("".to_string()));
 
-    let constructed: RcView = match layout.kind.as_ref() {
-        "linearlayout" => build_linear_layout(&layout, windows),
-        "textview" => build_text_view(&layout, windows),
-        _ => panic!("Unknown layout {}", layout.kind)
+    let view = match layout.kind.as_ref() {
+        "linearlayout" => build_linear_layout(layout, windows, pagers),
+        "textview" => build_text_view(layout, windows),
+        "grid" => build_grid(layout, windows, pagers),
+        "tabs" => build_tabs(layout, windows, pagers),
+        _ => {
+            // Config validation rejects these, so this should never happen. Leave a blank space.
+            warn!("Unknown layout {}", layout.kind);
+            Rc::new(RefCell::new(TextView::new(Dim::Fixed(0), Dim::Fixed(0))))
+        }
+    };
+
+    // Padding goes inside the border, along with the view it's put where it's asked, and the margin outside it
+    let (margin, border, _) = spacing(layout);
+    let view = match (layout.padding.unwrap_or(0), alignment(layout)) {
+        (0, (Align::Start, Align::Start)) => view,
+        (padding, (align, valign)) => {
+            let (width, height) = sized(layout, 2 * (margin + border));
+            let mut frame = Frame::new(view, padding, width, height);
+            frame.align(align, valign);
+            Rc::new(RefCell::new(frame))
+        }
+    };
+
+    let view: RcView = match border_style(layout) {
+        Some(style) => Rc::new(RefCell::new(Border::new(view, style))),
+        None => view
     };
 
-    return constructed;
+    match margin {
+        0 => view,
+        margin => {
+            let (width, height) = sized(layout, 0);
+            Rc::new(RefCell::new(Frame::new(view, margin, width, height)))
+        }
+    }
 }
 
-fn build_text_view(layout: &Layout, windows: &mut WindowMap) -> Rc<RefCell<dyn View>> {
-    let h_const = match layout.height {
-        Some(h) => Dim::Fixed(h),
-        None => Dim::WrapContent
+/// How much space a view's margin, border and padding each take on every side.
+fn spacing(layout: &Layout) -> (usize, usize, usize) {
+    let border = if border_style(layout).is_some() { 1 } else { 0 };
+    (layout.margin.unwrap_or(0), border, layout.padding.unwrap_or(0))
+}
+
+fn alignment(layout: &Layout) -> (Align, Align) {
+    let align = match layout.align.as_deref() {
+        Some("center") => Align::Center,
+        Some("right") => Align::End,
+        _ => Align::Start
     };
-    let w_const = match layout.width {
-        Some(w) => Dim::Fixed(w),
-        None => Dim::WrapContent
+    let valign = match layout.valign.as_deref() {
+        Some("middle") => Align::Center,
+        Some("bottom") => Align::End,
+        _ => Align::Start
     };
+    (align, valign)
+}
+
+fn border_style(layout: &Layout) -> Option<BorderStyle> {
+    match layout.border.as_deref() {
+        Some("single") => Some(BorderStyle::Single),
+        Some("double") => Some(BorderStyle::Double),
+        Some("rounded") => Some(BorderStyle::Rounded),
+        _ => None
+    }
+}
+
+/// The width and height constraints of what a view shows. Its margin, border and padding are inside the view's size,
+/// so it gets what's left. When it's aligned, it's sized by its content within that, so there's room to move it.
+fn constraints(layout: &Layout) -> (Dim, Dim) {
+    let (margin, border, padding) = spacing(layout);
+    let (width, height) = sized(layout, 2 * (margin + border + padding));
+    let (align, valign) = alignment(layout);
+    (loosen(width, align), loosen(height, valign))
+}
+
+/// The width and height constraints of a view, less `less`.
+fn sized(layout: &Layout, less: usize) -> (Dim, Dim) {
+    (dim(layout.width.as_ref(), layout.min_width, layout.max_width, less),
+     dim(layout.height.as_ref(), layout.min_height, layout.max_height, less))
+}
+
+/// A constraint which lets a view be smaller than it says, for it to be aligned other than at the start.
+fn loosen(dim: Dim, align: Align) -> Dim {
+    match (dim, align) {
+        (_, Align::Start) => dim,
+        (Dim::Fixed(most), _) | (Dim::Between(_, most), _) => Dim::UpTo(most),
+        (Dim::Percent(_), _) | (Dim::Weight(_), _) => Dim::WrapContent,
+        (dim, _) => dim
+    }
+}
+
+/// The constraint for a size, or the bounds on a size taken from the content - less `less`.
+fn dim(size: Option<&Size>, least: Option<usize>, most: Option<usize>, less: usize) -> Dim {
+    match (size.and_then(Size::extent), least, most) {
+        (Some(Extent::Chars(n)), _, _) => Dim::Fixed(n.saturating_sub(less)),
+        (Some(Extent::Percent(p)), _, _) => Dim::Percent(p),
+        (Some(Extent::Share(w)), _, _) => Dim::Weight(w),
+        (None, None, None) | (Some(Extent::Auto), None, None) => Dim::WrapContent,
+        (None, least, most) | (Some(Extent::Auto), least, most) => Dim::Between(least.unwrap_or(0).saturating_sub(less), most.unwrap_or(usize::MAX).saturating_sub(less))
+    }
+}
+
+fn build_text_view(layout: &Layout, windows: &mut WindowMap) -> Rc<RefCell<dyn View>> {
+    let (w_const, h_const) = constraints(layout);
 
     let task_id = layout.task_id.clone().unwrap_or(String::from("unknown"));
     trace!("Creating text view for {}", task_id);
+    let overflow = match layout.overflow.as_deref() {
+        Some("wrap") => Overflow::Wrap,
+        Some("wrap-words") => Overflow::WrapWords,
+        Some("ellipsis") => Overflow::Ellipsis,
+        _ => Overflow::Truncate
+    };
+
     let tv = Rc::new(RefCell::new(TextView::new(w_const, h_const)));
+    tv.borrow_mut().overflow(overflow);
+    tv.borrow_mut().id(&task_id);
     windows.insert(task_id.clone(), Rc::downgrade(&tv));
 
     tv
 }
 
-fn build_linear_layout(layout: &Layout, windows: &mut WindowMap) -> RcView {
-    let orientation = match layout.orientation.as_ref().unwrap().as_ref() {
-        "vertical" => Orientation::VERTICAL,
+fn build_linear_layout(layout: &Layout, windows: &mut WindowMap, pagers: &mut Vec<Pager>) -> RcView {
+    let orientation = match layout.orientation.as_deref() {
+        Some("vertical") => Orientation::VERTICAL,
         _ => Orientation::HORIZONTAL
     };
 
-    let h_const = match layout.height {
-        Some(h) => Dim::Fixed(h),
-        None => Dim::WrapContent
-    };
-
-    let w_const = match layout.width {
-        Some(w) => Dim::Fixed(w),
-        None => Dim::WrapContent
-    };
-
+    let (w_const, h_const) = constraints(layout);
     let mut ll: LinearLayout = LinearLayout::new(orientation, w_const, h_const);
 
     for child in layout.children.as_ref().unwrap_or(&Vec::new()) {
-        let child= construct_layout(&child, windows);
+        let child= construct_layout(child, windows, pagers);
         ll.add_child(child);
     }
 
     Rc::new(RefCell::new(ll))
 }
 
+fn build_grid(layout: &Layout, windows: &mut WindowMap, pagers: &mut Vec<Pager>) -> RcView {
+    // Tracks are sized like views, but "auto" ones fit the biggest child in them
+    let tracks = |sizes: Option<&Vec<Size>>| -> Vec<Dim> {
+        sizes.into_iter().flatten().map(|size| dim(Some(size), None, None, 0)).collect()
+    };
+
+    let (w_const, h_const) = constraints(layout);
+    let gap = layout.gap.unwrap_or(0);
+    let mut grid = Grid::new(tracks(layout.columns.as_ref()), tracks(layout.rows.as_ref()), (gap, gap), w_const, h_const);
+
+    for child in layout.children.as_ref().unwrap_or(&Vec::new()) {
+        let cell = grid.place(child.row, child.column, child.row_span.unwrap_or(1), child.column_span.unwrap_or(1));
+
+        // Views without a size fill their cell - unless it fits its content, when it'd be sized by them
+        let mut child = child.clone();
+        let (fit_across, fit_down) = grid.fits_content(&cell);
+        let fill = || Some(Size::Relative("fill".to_string()));
+        if !fit_across && child.width.is_none() && child.min_width.is_none() && child.max_width.is_none() { child.width = fill(); }
+        if !fit_down && child.height.is_none() && child.min_height.is_none() && child.max_height.is_none() { child.height = fill(); }
+
+        grid.add_child(construct_layout(&child, windows, pagers), cell);
+    }
+
+    Rc::new(RefCell::new(grid))
+}
+
+fn build_tabs(layout: &Layout, windows: &mut WindowMap, pagers: &mut Vec<Pager>) -> RcView {
+    let (w_const, h_const) = constraints(layout);
+    let tabs = Rc::new(RefCell::new(Tabs::new(w_const, h_const)));
+
+    for (i, child) in layout.children.iter().flatten().enumerate() {
+        // Pages are titled with what they say, or the task they show, or failing that their number
+        let title = child.title.clone().or_else(|| child.task_id.clone()).unwrap_or_else(|| format!("page {}", i + 1));
+        let page = construct_layout(child, windows, pagers);
+        tabs.borrow_mut().add_page(&title, page);
+    }
+
+    pagers.push(Pager::new(Rc::downgrade(&tabs), layout.rotate.as_deref().and_then(parse_duration)));
+    tabs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    const CONFIG: &str = r#"
+[[tasks]]
+    id = "load"
+    name = "Load"
+    description = ""
+    path = "."
+    command = "uptime"
+
+[[tasks]]
+    id = "disk"
+    name = "Disk"
+    description = ""
+    path = "."
+    command = "df"
+
+[layout]
+    kind = "linearlayout"
+    orientation = "horizontal"
+
+    [[layout.children]]
+        kind = "textview"
+        task_id = "disk"
+        main = true
+
+    [[layout.children]]
+        kind = "textview"
+        task_id = "load"
+"#;
+
+    fn ui(config: &str) -> CrossTermUiContext {
+        ui_from(config, PathBuf::new())
+    }
+
+    fn ui_from(config: &str, path: PathBuf) -> CrossTermUiContext {
+        let (event_sender, event_receiver) = mpsc::channel();
+        let (task_sender, _) = mpsc::channel();
+        let config = tasks::parse_task_config(config).unwrap();
+        let ui = CrossTermUiContext::new(config, path, event_receiver, event_sender, task_sender);
+        ui.top_view.borrow_mut().inflate(&(80, 24));
+        ui
+    }
+
+    fn key(c: char, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent { code: KeyCode::Char(c), modifiers })
+    }
+
+    #[test]
+    fn task_ids_can_be_typed_whatever_they_start_with() {
+        let mut ui = ui(CONFIG);
+        for c in "load".chars() { ui.handle_event(Event::ConsoleEdit(ConsoleEdit::Insert(c))); }
+        assert_eq!("load", ui.console_text);
+        assert_eq!(Some("disk"), ui.focused.as_deref());
+    }
+
+    #[test]
+    fn alt_and_hjkl_move_the_focus() {
+        let mut ui = ui(CONFIG);
+        ui.handle_event(key('l', KeyModifiers::ALT));
+        assert_eq!(Some("load"), ui.focused.as_deref());
+        ui.handle_event(key('h', KeyModifiers::ALT));
+        assert_eq!(Some("disk"), ui.focused.as_deref());
+        assert_eq!("", ui.console_text);
+    }
+
+    #[test]
+    fn alt_and_numbers_or_brackets_turn_pages() {
+        let config = CONFIG.replace("kind = \"linearlayout\"\n    orientation = \"horizontal\"", "kind = \"tabs\"");
+        let mut ui = ui(&config);
+        let page = |ui: &CrossTermUiContext| ui.pagers[0].tabs().upgrade().unwrap().borrow().current();
+
+        ui.handle_event(key('2', KeyModifiers::ALT));
+        assert_eq!(1, page(&ui));
+        assert_eq!(Some("load"), ui.focused.as_deref());
+        ui.handle_event(key('[', KeyModifiers::ALT));
+        assert_eq!(0, page(&ui));
+
+        for c in "1]".chars() { ui.handle_event(Event::ConsoleEdit(ConsoleEdit::Insert(c))); }
+        assert_eq!(0, page(&ui));
+        assert_eq!("1]", ui.console_text);
+    }
+
+    #[test]
+    fn reloading_keeps_the_output_of_tasks_which_run_the_same() {
+        let path = std::env::temp_dir().join(format!("fluxr-reload-{}.toml", std::process::id()));
+        std::fs::write(&path, CONFIG).unwrap();
+        let mut ui = ui_from(CONFIG, path.clone());
+        let shown = |ui: &CrossTermUiContext, id: &str| ui.windows[id].upgrade().unwrap().borrow().render().contains("output");
+        for id in ["disk", "load"] { ui.windows[id].upgrade().unwrap().borrow_mut().update_content(format!("{} output", id)); }
+
+        std::fs::write(&path, CONFIG.replace("name = \"Disk\"", "name = \"Disk space\"").replace("command = \"uptime\"", "command = \"cat /proc/loadavg\"")).unwrap();
+        ui.reload_config();
+        std::fs::remove_file(&path).unwrap_or(());
+        ui.top_view.borrow_mut().inflate(&(80, 24));
+
+        assert!(shown(&ui, "disk"));
+        assert!(!shown(&ui, "load"));
+    }
+
+    #[test]
+    fn views_of_tasks_run_by_others_say_what_runs_them() {
+        let config = CONFIG.replace("command = \"uptime\"", "command = \"uptime\"\n    after = [\"disk\"]").replace("horizontal", "vertical");
+        let ui = ui(&config);
+        let status = |id: &str| ui.windows[id].upgrade().unwrap().borrow().render_lines()[0].trim_end().to_string();
+        assert_eq!("-- never run - enter 'disk' to run it", status("disk"));
+        assert_eq!("-- not run yet - it runs after 'disk', or enter 'load' to run it", status("load"));
+    }
+}
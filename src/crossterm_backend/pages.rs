@@ -0,0 +1,55 @@
+use std::cell::RefCell;
+use std::rc::Weak;
+use std::time::{Duration, Instant};
+
+use crate::widgets::Tabs;
+
+/***
+Pager: Turns the pages of a tabs view - when asked, or by itself every so often if it rotates, for a screen nobody's
+    sitting at.
+ */
+pub struct Pager {
+    tabs: Weak<RefCell<Tabs>>,
+    rotate: Option<Duration>,
+    turned: Instant,
+}
+
+impl Pager {
+    pub fn new(tabs: Weak<RefCell<Tabs>>, rotate: Option<Duration>) -> Pager {
+        Pager { tabs, rotate, turned: Instant::now() }
+    }
+
+    pub fn tabs(&self) -> &Weak<RefCell<Tabs>> { &self.tabs }
+
+    /// When it's next due to turn the page by itself, if it does.
+    pub fn due(&self) -> Option<Instant> {
+        self.rotate.map(|every| self.turned + every)
+    }
+
+    /// Turn the page with `turn`. Turning it by hand puts off the next time it turns by itself.
+    pub fn turn(&mut self, turn: impl FnOnce(&mut Tabs)) {
+        if let Some(tabs) = self.tabs.upgrade() { turn(&mut tabs.borrow_mut()); }
+        self.turned = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use crate::widgets::{Dim, TextView};
+
+    #[test]
+    fn turning_puts_off_the_next_rotation() {
+        let tabs = Rc::new(RefCell::new(Tabs::new(Dim::WrapContent, Dim::WrapContent)));
+        for title in ["a", "b"] { tabs.borrow_mut().add_page(title, Rc::new(RefCell::new(TextView::new(Dim::WrapContent, Dim::WrapContent)))); }
+
+        let mut pager = Pager::new(Rc::downgrade(&tabs), Some(Duration::from_secs(30)));
+        let due = pager.due().unwrap();
+        pager.turn(Tabs::next);
+        assert_eq!(1, tabs.borrow().current());
+        assert!(pager.due().unwrap() >= due);
+
+        assert_eq!(None, Pager::new(Rc::downgrade(&tabs), None).due());
+    }
+}
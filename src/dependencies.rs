@@ -0,0 +1,299 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::executable_command::ExecutableCommand;
+use crate::run_result::RunResult;
+use crate::tasks::Task;
+use crate::TaskId;
+
+/***
+link: Gather every way of declaring a dependency into each task's `after` list.
+    `on_success = "b"` on task a is the same as `after = ["a"]` on task b, and reading
+    another task's output with `stdin_from` means running after it.
+ */
+pub fn link(tasks: &mut [Task]) {
+    let chained: Vec<(String, String)> = tasks.iter().
+        filter_map(|t| t.on_success.as_ref().map(|next| (next.clone(), t.id.clone()))).
+        collect();
+
+    for task in tasks.iter_mut() {
+        let upstream = chained.iter().filter(|(next, _)| *next == task.id).map(|(_, id)| id.clone());
+        let extra: Vec<String> = upstream.chain(task.stdin_from.clone()).collect();
+
+        for id in extra {
+            if !task.after.contains(&id) { task.after.push(id); }
+        }
+    }
+}
+
+/***
+find_cycle: A chain of tasks which (eventually) run after themselves, if there is one. Ex: [a, b, a]
+    Expects linked tasks. Dependencies on unknown tasks are ignored.
+ */
+pub fn find_cycle(tasks: &[Task]) -> Option<Vec<TaskId>> {
+    let upstream: HashMap<&str, &Vec<String>> = tasks.iter().map(|t| (t.id.as_str(), &t.after)).collect();
+    let mut done = HashSet::new();
+
+    for task in tasks {
+        let mut path = vec![];
+        if let Some(cycle) = visit(task.id.as_str(), &upstream, &mut path, &mut done) {
+            return Some(cycle);
+        }
+    }
+
+    None
+}
+
+fn visit<'a>(id: &'a str, upstream: &HashMap<&'a str, &'a Vec<String>>, path: &mut Vec<&'a str>, done: &mut HashSet<&'a str>) -> Option<Vec<TaskId>> {
+    if let Some(start) = path.iter().position(|p| *p == id) {
+        let mut cycle: Vec<TaskId> = path[start..].iter().map(|p| p.to_string()).collect();
+        cycle.push(id.to_string());
+        return Some(cycle);
+    }
+    if done.contains(id) { return None; }
+
+    path.push(id);
+    for dependency in upstream.get(id).map(|deps| deps.as_slice()).unwrap_or_default() {
+        if let Some(cycle) = visit(dependency.as_str(), upstream, path, done) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    done.insert(id);
+
+    None
+}
+
+/***
+Step: What to do with a task whose upstream tasks have all finished.
+    Run  -> They all succeeded, so run it
+    Skip -> One of them failed (or was itself skipped), so don't
+ */
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Run(TaskId),
+    Skip { id: TaskId, failed: TaskId },
+}
+
+/***
+Outputs: The stdout of each task's last run. Shared with the threads running tasks, so a task reading
+    another's output gets the latest however it comes to run.
+ */
+#[derive(Clone, Default)]
+pub struct Outputs(Arc<Mutex<HashMap<TaskId, String>>>);
+
+impl Outputs {
+    pub fn get(&self, id: &str) -> Option<String> {
+        self.0.lock().ok().and_then(|outputs| outputs.get(id).cloned())
+    }
+
+    fn set(&self, id: TaskId, output: String) {
+        if let Ok(mut outputs) = self.0.lock() { outputs.insert(id, output); }
+    }
+
+    fn retain(&self, keep: impl Fn(&TaskId) -> bool) {
+        if let Ok(mut outputs) = self.0.lock() { outputs.retain(|id, _| keep(id)); }
+    }
+}
+
+/***
+Pipeline: Decides when chained tasks run.
+    When a task finishes, everything downstream of it becomes pending. A pending task waits until
+    nothing upstream of it is still running or pending, so a task downstream of both sides of a
+    diamond runs once per round rather than once per branch.
+ */
+pub struct Pipeline {
+    upstream: HashMap<TaskId, Vec<TaskId>>,
+    results: HashMap<TaskId, RunResult>,
+    outputs: Outputs,
+    running: HashSet<TaskId>,
+    pending: BTreeSet<TaskId>,
+}
+
+impl Pipeline {
+    pub fn new(commands: &[ExecutableCommand]) -> Pipeline {
+        let mut pipeline = Pipeline { upstream: HashMap::new(), results: HashMap::new(), outputs: Outputs::default(), running: HashSet::new(), pending: BTreeSet::new() };
+        pipeline.update(commands);
+        pipeline
+    }
+
+    /// Swap in a reloaded set of tasks, remembering the last results of those which are still around.
+    pub fn update(&mut self, commands: &[ExecutableCommand]) {
+        self.upstream = commands.iter().map(|c| (c.id.clone(), c.after.clone())).collect();
+        let upstream = &self.upstream;
+        self.results.retain(|id, _| upstream.contains_key(id));
+        self.outputs.retain(|id| upstream.contains_key(id));
+        self.pending.retain(|id| upstream.contains_key(id));
+    }
+
+    /// The stdout of each task's last run, for piping into another.
+    pub fn outputs(&self) -> Outputs {
+        self.outputs.clone()
+    }
+
+    /// Record a run, returning what to do about everything downstream of it which is now ready.
+    /// Tasks given as Step::Run are taken to be running until they finish in turn.
+    pub fn finished(&mut self, result: RunResult) -> Vec<Step> {
+        self.running.remove(&result.id);
+        self.queue_downstream(&result.id);
+        self.record(result);
+
+        let mut steps = vec![];
+        while let Some(id) = self.pending.iter().find(|id| !self.waiting(id)).cloned() {
+            self.pending.remove(&id);
+            let upstream = self.upstream.get(&id).cloned().unwrap_or_default();
+
+            // Something upstream has never run, so there's nothing to go on yet. It'll be queued again when that finishes.
+            if upstream.iter().any(|u| !self.results.contains_key(u)) { continue; }
+
+            match upstream.iter().find(|u| !self.results[*u].succeeded()) {
+                Some(failed) => {
+                    // Skipping counts as failing, as far as anything further downstream is concerned
+                    let skipped = RunResult::skipped(&id, failed);
+                    steps.push(Step::Skip { id: id.clone(), failed: failed.clone() });
+                    self.queue_downstream(&id);
+                    self.record(skipped);
+                },
+                None => {
+                    self.running.insert(id.clone());
+                    steps.push(Step::Run(id));
+                }
+            }
+        }
+
+        steps
+    }
+
+    fn record(&mut self, result: RunResult) {
+        self.outputs.set(result.id.clone(), result.stdout.clone());
+        self.results.insert(result.id.clone(), result);
+    }
+
+    fn queue_downstream(&mut self, id: &str) {
+        let downstream = self.upstream.iter().filter(|(_, ups)| ups.iter().any(|u| u == id)).map(|(d, _)| d.clone());
+        self.pending.extend(downstream);
+    }
+
+    /// Whether anything upstream of a task, however far, is still to finish.
+    fn waiting(&self, id: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack: Vec<&str> = self.upstream.get(id).map(|u| u.iter().map(String::as_str).collect()).unwrap_or_default();
+
+        while let Some(up) = stack.pop() {
+            if !seen.insert(up) { continue; }
+            if self.running.contains(up) || self.pending.contains(up) { return true; }
+            stack.extend(self.upstream.get(up).map(|u| u.iter().map(String::as_str).collect::<Vec<&str>>()).unwrap_or_default());
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_result::Exit;
+    use crate::schedule::Schedule;
+    use std::time::{Duration, SystemTime};
+
+    fn command(id: &str, after: &[&str]) -> ExecutableCommand {
+        ExecutableCommand {
+            after: after.iter().map(|a| a.to_string()).collect(),
+            ..ExecutableCommand::new(id.to_string(), "true".to_string(), ".".to_string(), Schedule::Never)
+        }
+    }
+
+    fn result(id: &str, code: i32) -> RunResult {
+        RunResult {
+            id: id.to_string(),
+            exit: Exit::Code(code),
+            stdout: format!("{} out", id),
+            stderr: String::new(),
+            interleaved: String::new(),
+            started: SystemTime::now(),
+            duration: Duration::from_millis(1),
+        }
+    }
+
+    fn run(id: &str) -> Step { Step::Run(id.to_string()) }
+
+    #[test]
+    fn runs_downstream_tasks_in_order() {
+        let mut pipeline = Pipeline::new(&[command("build", &[]), command("test", &["build"]), command("deploy", &["test"])]);
+
+        assert_eq!(pipeline.finished(result("build", 0)), vec![run("test")]);
+        assert_eq!(pipeline.finished(result("test", 0)), vec![run("deploy")]);
+        assert_eq!(pipeline.finished(result("deploy", 0)), vec![]);
+        assert_eq!(pipeline.outputs().get("build"), Some("build out".to_string()));
+    }
+
+    #[test]
+    fn outputs_handed_out_earlier_stay_current() {
+        let mut pipeline = Pipeline::new(&[command("build", &[]), command("test", &["build"])]);
+        let outputs = pipeline.outputs();
+
+        pipeline.finished(result("build", 0));
+        assert_eq!(outputs.get("build"), Some("build out".to_string()));
+
+        pipeline.update(&[command("test", &[])]);
+        assert_eq!(outputs.get("build"), None);
+    }
+
+    #[test]
+    fn skips_everything_downstream_of_a_failure() {
+        let mut pipeline = Pipeline::new(&[command("build", &[]), command("test", &["build"]), command("deploy", &["test"])]);
+
+        assert_eq!(pipeline.finished(result("build", 1)), vec![
+            Step::Skip { id: "test".to_string(), failed: "build".to_string() },
+            Step::Skip { id: "deploy".to_string(), failed: "test".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn diamonds_run_their_bottom_once() {
+        let mut pipeline = Pipeline::new(&[
+            command("a", &[]), command("b", &["a"]), command("c", &["a"]), command("d", &["b", "c"]),
+        ]);
+
+        assert_eq!(pipeline.finished(result("a", 0)), vec![run("b"), run("c")]);
+        assert_eq!(pipeline.finished(result("b", 0)), vec![]);
+        assert_eq!(pipeline.finished(result("c", 0)), vec![run("d")]);
+    }
+
+    #[test]
+    fn waits_for_upstream_tasks_which_have_never_run() {
+        let mut pipeline = Pipeline::new(&[command("a", &[]), command("b", &[]), command("c", &["a", "b"])]);
+
+        assert_eq!(pipeline.finished(result("a", 0)), vec![]);
+        assert_eq!(pipeline.finished(result("b", 0)), vec![run("c")]);
+    }
+
+    fn task(id: &str, after: &[&str], on_success: Option<&str>, stdin_from: Option<&str>) -> Task {
+        let toml = format!("id = \"{}\"\nname = \"\"\ndescription = \"\"\npath = \".\"\ncommand = \"true\"\nafter = {:?}\n{}{}",
+                           id, after,
+                           on_success.map(|s| format!("on_success = \"{}\"\n", s)).unwrap_or_default(),
+                           stdin_from.map(|s| format!("stdin_from = \"{}\"\n", s)).unwrap_or_default());
+        toml::from_str(&toml).unwrap()
+    }
+
+    #[test]
+    fn links_every_kind_of_dependency() {
+        let mut tasks = vec![task("build", &[], Some("test"), None), task("test", &["lint"], None, Some("report")),
+                             task("lint", &[], None, None), task("report", &[], None, None)];
+        link(&mut tasks);
+        assert_eq!(tasks[1].after, vec!["lint", "build", "report"]);
+    }
+
+    #[test]
+    fn finds_cycles() {
+        let mut tasks = vec![task("a", &["c"], None, None), task("b", &["a"], None, None), task("c", &[], None, Some("b"))];
+        link(&mut tasks);
+        assert_eq!(find_cycle(&tasks), Some(vec!["a".to_string(), "c".to_string(), "b".to_string(), "a".to_string()]));
+
+        let tasks = vec![task("a", &["a"], None, None)];
+        assert_eq!(find_cycle(&tasks), Some(vec!["a".to_string(), "a".to_string()]));
+
+        let tasks = vec![task("a", &[], None, None), task("b", &["a", "nope"], None, None)];
+        assert_eq!(find_cycle(&tasks), None);
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Environment variables whose names contain any of these are kept out of the logs.
+const SECRET_MARKERS: [&str; 7] = ["SECRET", "TOKEN", "PASSWORD", "PASSWD", "KEY", "CREDENTIAL", "AUTH"];
+const MASK: &str = "****";
+
+/***
+load_env_file: Read KEY=VALUE pairs from a dotenv-style file.
+ */
+pub fn load_env_file(path: &Path) -> Result<BTreeMap<String, String>, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+    parse_env(&contents).map_err(|err| format!("{}:{}", path.display(), err))
+}
+
+/***
+parse_env: Parse dotenv-style text.
+    Blank lines and lines starting with '#' are skipped, a leading 'export ' is ignored,
+    and values may be wrapped in single or double quotes.
+ */
+pub fn parse_env(contents: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut env = BTreeMap::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line.split_once('=').ok_or_else(|| format!("{}: expected KEY=VALUE", i + 1))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("{}: bad variable name '{}'", i + 1, key));
+        }
+
+        env.insert(key.to_string(), unquote(value.trim()).to_string());
+    }
+
+    Ok(env)
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in &["\"", "'"] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+pub fn is_secret(name: &str) -> bool {
+    let name = name.to_uppercase();
+    SECRET_MARKERS.iter().any(|marker| name.contains(marker))
+}
+
+/***
+mask_secrets: Hide the values of any secret-looking variables wherever they appear in `text`.
+ */
+pub fn mask_secrets(text: &str, env: &BTreeMap<String, String>) -> String {
+    env.iter().
+        filter(|(name, value)| is_secret(name) && !value.is_empty()).
+        fold(text.to_string(), |masked, (_, value)| masked.replace(value.as_str(), MASK))
+}
+
+/***
+describe_env: A loggable summary of an environment, with secret values masked.
+ */
+pub fn describe_env(env: &BTreeMap<String, String>) -> String {
+    env.iter().
+        map(|(name, value)| format!("{}={}", name, if is_secret(name) { MASK } else { value })).
+        collect::<Vec<String>>().
+        join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dotenv_files() {
+        let env = parse_env("# creds\n\nexport API_TOKEN=\"abc 123\"\nREGION = us-east-1\nNAME='fluxr'\n").unwrap();
+        assert_eq!(env.get("API_TOKEN").map(String::as_str), Some("abc 123"));
+        assert_eq!(env.get("REGION").map(String::as_str), Some("us-east-1"));
+        assert_eq!(env.get("NAME").map(String::as_str), Some("fluxr"));
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        assert_eq!(parse_env("A=1\nnonsense\n"), Err("2: expected KEY=VALUE".to_string()));
+        assert_eq!(parse_env("BAD NAME=1"), Err("1: bad variable name 'BAD NAME'".to_string()));
+    }
+
+    #[test]
+    fn masks_only_secret_values() {
+        let env = parse_env("DB_PASSWORD=hunter2\nUSER=lucas").unwrap();
+        assert_eq!(mask_secrets("login lucas hunter2", &env), "login lucas ****");
+        assert_eq!(describe_env(&env), "DB_PASSWORD=**** USER=lucas");
+    }
+}
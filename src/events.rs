@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use crossterm::event::KeyEvent;
+
+use crate::process::Stream;
+use crate::run_result::{Exit, RunResult};
+use crate::TaskId;
+
+/***
+Event: Everything the UI reacts to, whether from the TaskRunner, the keyboard or the system.
+ */
+#[derive(Debug)]
+pub enum Event {
+    TaskOutput(RunResult),
+    TaskLine(TaskId, Stream, String),
+    StreamEnded { id: TaskId, exit: Exit, restart_in: Duration },
+    Key(KeyEvent),
+    Resize(u16, u16),
+    ConsoleEdit(ConsoleEdit),
+    ConfigChanged,
+    Quit,
+}
+
+/***
+ConsoleEdit: Changes to the command console's text.
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConsoleEdit {
+    Insert(char),
+    Backspace,
+    Clear,
+    Submit,
+}
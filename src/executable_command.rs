@@ -1,95 +1,114 @@
-use std::str;
-use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::convert::TryFrom;
+use std::time::Duration;
 
+use chrono::{DateTime, Local};
+
+use crate::schedule::{Clock, Schedule};
+use crate::TaskId;
+
+#[derive(PartialEq, Debug, Clone)]
 pub struct ExecutableCommand {
     pub id: String,
     pub command: String,
     pub working_dir: String,
-    pub period: String,
-    pub time_between_runs: u64
+    pub schedule: Schedule,
+    pub timeout: Option<Duration>,
+    pub shell: Option<String>,
+    pub env: BTreeMap<String, String>,
+    pub env_file: Option<PathBuf>,
+    pub clear_env: bool,
+    pub watch: Vec<String>,
+    pub after: Vec<TaskId>,
+    pub stdin_from: Option<TaskId>,
+    pub streaming: bool,
+    pub pty: bool,
 }
 
 impl ExecutableCommand {
-    pub fn new(id: String, command: String, working_dir: String, period: String) -> ExecutableCommand {
+    pub fn new(id: String, command: String, working_dir: String, schedule: Schedule) -> ExecutableCommand {
         ExecutableCommand {
             id,
             command,
             working_dir,
-            period: period.clone(),
-            time_between_runs: calc_time_between_runs(period.as_str()),
-        }
-    }
-
-    pub fn millis_until_next_run(&self, elapsed: u64) -> u64 {
-        match elapsed > self.time_between_runs
-        {
-            true => 0,
-            false => self.time_between_runs - elapsed
+            schedule,
+            timeout: None,
+            shell: None,
+            env: BTreeMap::new(),
+            env_file: None,
+            clear_env: false,
+            watch: vec![],
+            after: vec![],
+            stdin_from: None,
+            streaming: false,
+            pty: false,
         }
-
     }
-}
-
-impl Clone for ExecutableCommand {
-    fn clone(&self) -> ExecutableCommand {
-        ExecutableCommand::new(
-            self.id.clone(),
-            self.command.clone(),
-            self.working_dir.clone(),
-            self.period.clone()
-        )
-    }
-}
-
-fn calc_time_between_runs(period: &str) -> u64 {
-    let matcher = Regex::new(r"(\d+)([smh]?)").unwrap();
 
-    for c in matcher.captures_iter(period) {
-        // Should only be one, but whatever.
-        let time = &c[1].parse::<u64>().unwrap();
-        let unit = &c[2];
-
-        let mult = match unit {
-            "h" => 3600000,
-            "m" => 60000,
-            _ => 1000 // default to milliseconds
-        };
-
-        return time * mult;
+    /// How long to sleep before running again, given the last run started at `last_run`.
+    /// Rounded up, so a cron task never wakes just before its minute and runs twice.
+    pub fn millis_until_next_run(&self, last_run: DateTime<Local>, clock: &dyn Clock) -> u64 {
+        let wait = self.schedule.time_until_next_run(last_run, clock.now());
+        let millis = wait.as_nanos().div_ceil(1_000_000);
+        u64::try_from(millis).unwrap_or(u64::MAX)
     }
-
-    panic!("Couldn't calculate the time between runs from '{}'", period);
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::schedule::FakeClock;
 
-    #[test]
-    fn time_between_runs_works_for_seconds() {
-        assert_eq!(calc_time_between_runs("1s"), 1000);
+    fn command(schedule: &str) -> ExecutableCommand {
+        ExecutableCommand::new("test".to_string(), "date".to_string(), ".".to_string(), Schedule::parse(schedule).unwrap())
     }
 
     #[test]
-    fn time_between_runs_assumes_seconds() {
-        assert_eq!(calc_time_between_runs("12"), 12000);
+    fn periodic_tasks_wait_for_the_rest_of_their_period() {
+        let clock = FakeClock::at(2026, 3, 2, 12, 0, 0);
+        let cmd = command("1m");
+        let last_run = clock.now();
+
+        clock.advance(Duration::from_secs(15));
+        assert_eq!(cmd.millis_until_next_run(last_run, &clock), 45000);
     }
 
     #[test]
-    fn time_between_runs_works_for_minutes() {
-        assert_eq!(calc_time_between_runs("1m"), 60000);
+    fn overdue_tasks_run_straight_away() {
+        let clock = FakeClock::at(2026, 3, 2, 12, 0, 0);
+        let cmd = command("500ms");
+        let last_run = clock.now();
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cmd.millis_until_next_run(last_run, &clock), 0);
     }
 
     #[test]
-    fn time_between_runs_works_for_hours() {
-        assert_eq!(calc_time_between_runs("1h"), 3600000);
+    fn cron_tasks_wait_for_the_next_matching_time() {
+        let clock = FakeClock::at(2026, 3, 2, 23, 30, 0);
+        let cmd = command("@daily");
+        assert_eq!(cmd.millis_until_next_run(clock.now(), &clock), 30 * 60 * 1000);
     }
 
     #[test]
-    #[should_panic]
-    fn time_between_panics_for_bad_pattern() {
-        calc_time_between_runs("m");
+    fn cron_tasks_ignore_how_long_they_ran_for() {
+        let clock = FakeClock::at(2026, 3, 2, 9, 0, 0);
+        let cmd = command("*/5 * * * *");
+        let last_run = clock.now();
+
+        clock.advance(Duration::from_millis(3500));
+        assert_eq!(cmd.millis_until_next_run(last_run, &clock), 5 * 60 * 1000 - 3500);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn waits_are_rounded_up_to_the_next_millisecond() {
+        let clock = FakeClock::at(2026, 3, 2, 9, 0, 0);
+        let cmd = command("1s");
+        let last_run = clock.now();
+
+        clock.advance(Duration::from_micros(100));
+        assert_eq!(cmd.millis_until_next_run(last_run, &clock), 1000);
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use glob::{MatchOptions, Pattern};
+use log::warn;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+// '*' stops at directory boundaries, '**' doesn't
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/***
+WatchPattern: A glob like "src/**/*.rs", split into the directory to watch and the pattern to match in it.
+ */
+#[derive(Debug)]
+struct WatchPattern {
+    dir: PathBuf,
+    pattern: Pattern,
+}
+
+/***
+watch_files: Send a message whenever a file matching any of `patterns` is created, changed or removed.
+    Patterns are relative to `working_dir`. Watching stops when the returned watcher is dropped.
+ */
+pub fn watch_files(patterns: &[String], working_dir: &str, changed: Sender<()>) -> notify::Result<RecommendedWatcher> {
+    let patterns = patterns.iter().
+        map(|p| watch_pattern(&Path::new(working_dir).join(p))).
+        collect::<notify::Result<Vec<WatchPattern>>>()?;
+    let matchers: Vec<Pattern> = patterns.iter().map(|p| p.pattern.clone()).collect();
+
+    let mut watcher = RecommendedWatcher::new(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) => {
+                let relevant = !event.kind.is_access() &&
+                    event.paths.iter().any(|path| matchers.iter().any(|m| m.matches_path_with(path, MATCH_OPTIONS)));
+
+                // If nobody's listening, the task's loop has stopped and this watcher is about to go too
+                if relevant { changed.send(()).unwrap_or(()); }
+            },
+            Err(err) => warn!("Error watching files: {}", err)
+        }
+    }, notify::Config::default())?;
+
+    for pattern in &patterns {
+        watcher.watch(&pattern.dir, RecursiveMode::Recursive)?;
+    }
+
+    Ok(watcher)
+}
+
+/***
+check_pattern: Whether a watch pattern is valid glob syntax. Returns why not if it isn't.
+ */
+pub fn check_pattern(pattern: &str) -> Result<(), String> {
+    Pattern::new(pattern).map(|_| ()).map_err(|err| err.msg.to_string())
+}
+
+/// Notify reports canonical paths, so the literal part of the pattern has to be canonical too.
+fn watch_pattern(pattern: &Path) -> notify::Result<WatchPattern> {
+    let (dir, rest) = split_literal_prefix(pattern);
+    let dir = dir.canonicalize()?;
+    let pattern = Pattern::new(&dir.join(rest).to_string_lossy()).
+        map_err(|err| notify::Error::generic(err.msg))?;
+
+    Ok(WatchPattern { dir, pattern })
+}
+
+/// Split a pattern at its first component with a wildcard - "src/**/*.rs" becomes ("src", "**/*.rs").
+/// Without any wildcards the pattern is a single file, so we watch the directory it's in.
+fn split_literal_prefix(pattern: &Path) -> (PathBuf, PathBuf) {
+    let components: Vec<Component> = pattern.components().collect();
+    let is_glob = |c: &Component| c.as_os_str().to_string_lossy().contains(|ch| "*?[".contains(ch));
+    let split = components.iter().position(is_glob).unwrap_or(components.len().saturating_sub(1));
+
+    let mut dir: PathBuf = components[..split].iter().collect();
+    if dir.as_os_str().is_empty() { dir.push("."); }
+    (dir, components[split..].iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn splits_off_the_directory_to_watch() {
+        assert_eq!(split_literal_prefix(Path::new("./src/**/*.rs")), (PathBuf::from("./src"), PathBuf::from("**/*.rs")));
+        assert_eq!(split_literal_prefix(Path::new("/opt/app/Cargo.toml")), (PathBuf::from("/opt/app"), PathBuf::from("Cargo.toml")));
+        assert_eq!(split_literal_prefix(Path::new("*.toml")), (PathBuf::from("."), PathBuf::from("*.toml")));
+    }
+
+    #[test]
+    fn stars_stay_within_a_directory() {
+        let pattern = Pattern::new("/src/*.rs").unwrap();
+        assert!(pattern.matches_path_with(Path::new("/src/main.rs"), MATCH_OPTIONS));
+        assert!(!pattern.matches_path_with(Path::new("/src/widgets/mod.rs"), MATCH_OPTIONS));
+        assert!(Pattern::new("/src/**/*.rs").unwrap().matches_path_with(Path::new("/src/widgets/mod.rs"), MATCH_OPTIONS));
+    }
+
+    #[test]
+    fn reports_bad_patterns() {
+        assert!(check_pattern("src/**/*.rs").is_ok());
+        assert!(check_pattern("src/[").is_err());
+    }
+
+    #[test]
+    fn signals_when_matching_files_change() {
+        let dir = std::env::temp_dir().join(format!("fluxr-watch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _watcher = watch_files(&["src/*.rs".to_string()], &dir.to_string_lossy(), tx).unwrap();
+
+        fs::write(dir.join("src/notes.txt"), "ignored").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        assert!(rx.recv_timeout(Duration::from_secs(2)).is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
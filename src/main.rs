@@ -4,24 +4,37 @@ extern crate regex;
 extern crate simplelog;
 extern crate crossterm;
 
-use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
 use std::thread;
 
+use clap::Parser;
 use simplelog::*;
-use log::info;
+use log::{info, warn};
 
-use crate::runner::TaskRunner;
-use crate::tasks::Layout;
+use crate::cli::{Mode, Opts};
+use crate::runner::{RunnerCommand, TaskRunner};
 use std::thread::JoinHandle;
 use crate::crossterm_backend::CrossTermUiContext;
+use crate::events::Event;
 
 
+mod cli;
+mod config_watcher;
+mod dependencies;
+mod file_watcher;
+mod env_file;
+mod events;
 mod tasks;
+mod validation;
 mod executable_command;
 mod runner;
+mod process;
+mod pty;
+mod run_result;
+mod schedule;
 mod crossterm_backend;
 
 pub type TaskId = String;
@@ -42,41 +55,75 @@ impl<T> Channel<T> {
 }
 
 fn main() {
-    init_logging();
-
-    let config = tasks::load_task_config().unwrap();
-    let layout = config.layout;
+    let opts = Opts::parse();
+
+    let config = match tasks::load_task_config(&opts.config) {
+        Ok(config) => config,
+        Err(errors) => {
+            for err in &errors {
+                match err.location {
+                    Some(_) => eprintln!("{}:{}", opts.config.display(), err),
+                    None => eprintln!("{}: {}", opts.config.display(), err),
+                }
+            }
+            eprintln!("{} has {} problem(s)", opts.config.display(), errors.len());
+            std::process::exit(1);
+        }
+    };
+
+    match opts.mode() {
+        Mode::Check => println!("{} is valid: {} tasks", opts.config.display(), config.tasks.len()),
+        Mode::PrintLayout => print!("{}", config.layout),
+        Mode::Run => {
+            init_logging(&opts.log, opts.log_level);
+            run_dashboard(config, opts.config);
+        }
+    }
+}
 
-    let system_command_channel = Channel::from(mpsc::channel());
+fn run_dashboard(config: tasks::Config, config_path: PathBuf) {
+    let event_channel = Channel::from(mpsc::channel());
     let task_running_channel = Channel::from(mpsc::channel());
 
-    let mut runner = TaskRunner::new(config.tasks, system_command_channel.tx.clone(), task_running_channel.rx);
+    let mut runner = TaskRunner::new(config.tasks.clone(), event_channel.tx.clone(), task_running_channel.tx.clone(), task_running_channel.rx);
+
+    let runner_thread = thread::spawn( move || { runner.run(); });
+    let runner_sender = task_running_channel.tx.clone();
+
+    if let Err(err) = config_watcher::watch_config(&config_path, event_channel.tx.clone()) {
+        warn!("Can't watch {} for changes: {}", config_path.display(), err);
+    }
 
-    thread::spawn( move || { runner.run(); });
+    launch_crossterm(config,
+                     config_path,
+                     event_channel.rx,
+                     event_channel.tx,
+                     task_running_channel.tx).join().unwrap_or(());
 
-    launch_crossterm(layout,
-                     system_command_channel.rx,
-                     system_command_channel.tx,
-                     task_running_channel.tx.clone()).join().unwrap_or({});
+    runner_sender.send(RunnerCommand::Shutdown).unwrap_or(());
+    runner_thread.join().unwrap_or(());
 }
 
-fn launch_crossterm(layout: Layout,
-                    command_receiver: Receiver<HashMap<String, String>>,
-                    command_sender: Sender<HashMap<String, String>>,
-                    task_sender: Sender<String>) -> JoinHandle<()> {
+fn launch_crossterm(config: tasks::Config,
+                    config_path: PathBuf,
+                    event_receiver: Receiver<Event>,
+                    event_sender: Sender<Event>,
+                    task_sender: Sender<RunnerCommand>) -> JoinHandle<()> {
     thread::spawn(move || {
         info!("Setting up crossterm!");
-        let mut ctx = CrossTermUiContext::new(layout, command_receiver, command_sender, task_sender);
+        let mut ctx = CrossTermUiContext::new(config, config_path, event_receiver, event_sender, task_sender);
         ctx.run_ui_loop();
     })
 }
 
-fn init_logging() {
+fn init_logging(log_path: &Path, level: LevelFilter) {
+    if let Some(dir) = log_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).unwrap();
+    }
+
     CombinedLogger::init(
         vec![
-            WriteLogger::new(LevelFilter::Info, Config::default(), File::create("log/flux.log").unwrap()),
+            WriteLogger::new(level, Config::default(), File::create(log_path).unwrap()),
         ]
     ).unwrap();
 }
-
-
@@ -0,0 +1,433 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::str;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::env_file::{describe_env, load_env_file, mask_secrets};
+use crate::executable_command::ExecutableCommand;
+use crate::pty::{self, Pty, WindowSize};
+use crate::run_result::Exit;
+
+// How often we check on a running process
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How long a timed-out process gets to clean up after SIGTERM before we SIGKILL it
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(3);
+// What commands in a pty are told they're talking to, unless their environment says otherwise
+const TERMINAL_TYPE: &str = "xterm-256color";
+
+#[derive(Default)]
+pub struct CapturedOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub interleaved: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+// A running command's output, a line at a time, from whichever stream it was written to
+type Lines = Receiver<(Stream, String)>;
+
+/***
+exec_command: Run a command to completion, capturing its output.
+    The command gets its own process group, so if it outlives its timeout we can take down
+    anything it spawned along with it. `stdin` is fed to the command if given, otherwise it gets none.
+    Commands run in a pty get a terminal of size `window`.
+ */
+pub fn exec_command(cmd: &ExecutableCommand, stdin: Option<&str>, window: WindowSize) -> io::Result<(Exit, CapturedOutput)> {
+    let (mut child, lines, _terminal) = spawn(cmd, stdin, window)?;
+    supervise(&mut child, lines, cmd.timeout)
+}
+
+/***
+stream_command: Run a command until it exits, handing over each line of output as soon as it's written.
+    From a pty, output is handed over as it arrives instead, whole lines or not.
+    Stops early - taking the command down with it - if `on_line` returns false or `stopped` returns true.
+    Commands run in a pty are told whenever `window` changes.
+ */
+pub fn stream_command(cmd: &ExecutableCommand,
+                      window: impl Fn() -> WindowSize,
+                      mut on_line: impl FnMut(Stream, String) -> bool,
+                      stopped: impl Fn() -> bool) -> io::Result<Exit> {
+    let mut size = window();
+    let (mut child, lines, terminal) = spawn(cmd, None, size)?;
+    let mut streams_open = true;
+
+    loop {
+        if streams_open {
+            match lines.recv_timeout(POLL_INTERVAL) {
+                Ok((stream, line)) => if !on_line(stream, line) { break; },
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => streams_open = false,
+            }
+        } else {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        if let Some(status) = child.try_wait()? {
+            if !streams_open { return Ok(exit_from(status)); }
+        }
+
+        if stopped() { break; }
+
+        if let Some(terminal) = &terminal {
+            if window() != size {
+                size = window();
+                pty::resize(terminal, size).unwrap_or_else(|err| warn!("Couldn't resize {}'s terminal: {}", cmd.id, err));
+            }
+        }
+    }
+
+    terminate(&mut child)
+}
+
+/***
+spawn: Start a command with its output going to reader threads, which send it on line by line.
+    In a pty, everything the command writes comes back through the terminal as stdout. The terminal is
+    returned too, so it can be resized - the command itself only holds the other end.
+ */
+fn spawn(cmd: &ExecutableCommand, stdin: Option<&str>, window: WindowSize) -> io::Result<(Child, Lines, Option<File>)> {
+    let argv = command_line(&cmd.command, cmd.shell.as_deref())?;
+    let program = resolve_program(&argv[0], &cmd.working_dir);
+    let mut env = environment(cmd)?;
+
+    info!("Running {}", mask_secrets(&format!("{} {:?} in {} with {}{}{}", program, &argv[1..], cmd.working_dir,
+                                               if cmd.clear_env { "only " } else { "" }, describe_env(&env),
+                                               if cmd.pty { format!(" in a {}x{} pty", window.cols, window.rows) } else { String::new() }), &env));
+
+    let mut command = Command::new(program);
+    if cmd.clear_env { command.env_clear(); }
+
+    command.
+        current_dir(&cmd.working_dir).
+        args(&argv[1..]);
+
+    let pty = match cmd.pty {
+        true => {
+            let pty = Pty::open(window)?;
+            pty.attach(&mut command)?;
+            env.entry("TERM".to_string()).or_insert_with(|| TERMINAL_TYPE.to_string());
+            Some(pty)
+        },
+        false => {
+            command.
+                stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() }).
+                stdout(Stdio::piped()).
+                stderr(Stdio::piped()).
+                process_group(0);
+            None
+        }
+    };
+
+    let mut child = command.envs(&env).spawn()?;
+    drop(command); // It holds copies of the pty's command end, which would keep it open after the command exits
+
+    if let (Some(mut pipe), Some(input)) = (child.stdin.take(), stdin) {
+        let input = input.to_string();
+        // The command may not read it all - or at all - so don't let a full pipe hold us up
+        thread::spawn(move || pipe.write_all(input.as_bytes()).unwrap_or(()));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let terminal = match pty {
+        Some(pty) => {
+            let master = pty.into_master();
+            spawn_terminal_reader(master.try_clone()?, tx.clone());
+            Some(master)
+        },
+        None => {
+            if let Some(stdout) = child.stdout.take() { spawn_reader(stdout, Stream::Stdout, tx.clone()); }
+            if let Some(stderr) = child.stderr.take() { spawn_reader(stderr, Stream::Stderr, tx.clone()); }
+            None
+        }
+    };
+    drop(tx); // Otherwise we'd wait on ourselves forever
+
+    Ok((child, rx, terminal))
+}
+
+/***
+environment: The variables to set for a command, on top of (or instead of, with clear_env) our own.
+    The env file is re-read every run, so rotated credentials are picked up without a reload.
+    Variables set directly on the task win over those from its env file.
+ */
+fn environment(cmd: &ExecutableCommand) -> io::Result<BTreeMap<String, String>> {
+    let mut env = match &cmd.env_file {
+        Some(path) => load_env_file(path).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+        None => BTreeMap::new()
+    };
+
+    env.extend(cmd.env.clone());
+    Ok(env)
+}
+
+/***
+command_line: Split a command into the program to run and its arguments.
+    With a shell, the whole command goes to the shell as-is, so pipes, globs and $VARIABLES work.
+    Without one, we split on whitespace the way a shell would - respecting quotes and escapes.
+ */
+pub fn command_line(command: &str, shell: Option<&str>) -> io::Result<Vec<String>> {
+    let argv = match shell {
+        Some(shell) => vec![shell.to_string(), "-c".to_string(), command.to_string()],
+        None => shell_words::split(command).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+    };
+
+    if argv.is_empty() { return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command")); }
+
+    Ok(argv)
+}
+
+/***
+resolve_program: Work out what to execute.
+    Bare names (`date`) are looked up on $PATH. Paths (`./build.sh`) are relative to the task's working dir.
+ */
+fn resolve_program(program: &str, working_dir: &str) -> String {
+    if program.contains('/') {
+        Path::new(working_dir).join(program).to_string_lossy().into_owned()
+    } else {
+        program.to_string()
+    }
+}
+
+/***
+supervise: Collect a child's output until it exits, killing it if it runs past its timeout.
+    Lines from both streams arrive on one channel, so we can record the order they were written in.
+ */
+fn supervise(child: &mut Child, lines: Lines, timeout: Option<Duration>) -> io::Result<(Exit, CapturedOutput)> {
+    let started = Instant::now();
+    let mut captured = CapturedOutput::default();
+    let mut streams_open = true;
+    let mut terminated_at: Option<Instant> = None;
+    let mut killed = false;
+
+    let status = loop {
+        if streams_open {
+            match lines.recv_timeout(POLL_INTERVAL) {
+                Ok((stream, line)) => {
+                    captured.interleaved += &line;
+                    match stream {
+                        Stream::Stdout => captured.stdout += &line,
+                        Stream::Stderr => captured.stderr += &line,
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => streams_open = false,
+            }
+        } else {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        if let Some(status) = child.try_wait()? {
+            // Once killed, don't hang around for anything that escaped the process group holding the pipes open
+            if !streams_open || killed { break status; }
+        }
+
+        match (timeout, terminated_at) {
+            (Some(timeout), None) if started.elapsed() >= timeout => {
+                warn!("{} timed out after {:.2?}", child.id(), timeout);
+                signal_group(child, libc::SIGTERM);
+                terminated_at = Some(Instant::now());
+            },
+            (_, Some(at)) if !killed && at.elapsed() >= KILL_GRACE_PERIOD => {
+                warn!("{} ignored SIGTERM - killing it", child.id());
+                signal_group(child, libc::SIGKILL);
+                killed = true;
+            },
+            _ => {}
+        }
+    };
+
+    let exit = match (timeout, terminated_at) {
+        (Some(timeout), Some(_)) => Exit::TimedOut(timeout),
+        _ => exit_from(status)
+    };
+
+    Ok((exit, captured))
+}
+
+/// Politely, then not so politely, stop a command and everything it started.
+fn terminate(child: &mut Child) -> io::Result<Exit> {
+    signal_group(child, libc::SIGTERM);
+
+    let terminated_at = Instant::now();
+    while terminated_at.elapsed() < KILL_GRACE_PERIOD {
+        if let Some(status) = child.try_wait()? { return Ok(exit_from(status)); }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    warn!("{} ignored SIGTERM - killing it", child.id());
+    signal_group(child, libc::SIGKILL);
+    Ok(exit_from(child.wait()?))
+}
+
+fn exit_from(status: ExitStatus) -> Exit {
+    match status.code() {
+        Some(code) => Exit::Code(code),
+        None => Exit::Signal(status.signal().unwrap_or(0))
+    }
+}
+
+fn signal_group(child: &Child, signal: libc::c_int) {
+    // The child leads its own process group, so the group id is its pid. Negating it targets the whole group.
+    let pgid = child.id() as libc::pid_t;
+    if unsafe { libc::kill(-pgid, signal) } != 0 {
+        warn!("Couldn't signal process group {}: {}", pgid, io::Error::last_os_error());
+    }
+}
+
+fn spawn_reader<R: Read + Send + 'static>(source: R, stream: Stream, tx: Sender<(Stream, String)>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(source);
+        let mut line = vec![];
+        while let Ok(n) = reader.read_until(b'\n', &mut line) {
+            if n == 0 || tx.send((stream, String::from_utf8_lossy(&line).into_owned())).is_err() { break; }
+            line.clear();
+        }
+    });
+}
+
+/***
+spawn_terminal_reader: Pass on whatever's written to a terminal as soon as it arrives, rather than a line at a time -
+    programs redrawing a progress bar or a full screen might never finish a line. Characters split between reads
+    are held back until they're whole. Reading fails, rather than ending, once the command's gone.
+ */
+fn spawn_terminal_reader(mut terminal: File, tx: Sender<(Stream, String)>) {
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        let mut pending = vec![];
+        loop {
+            match terminal.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => pending.extend_from_slice(&buffer[..n]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break
+            }
+
+            let complete = match str::from_utf8(&pending) {
+                Err(err) if err.error_len().is_none() => err.valid_up_to(),
+                _ => pending.len()
+            };
+            let rest = pending.split_off(complete);
+            if !pending.is_empty() && tx.send((Stream::Stdout, String::from_utf8_lossy(&pending).into_owned())).is_err() { return; }
+            pending = rest;
+        }
+
+        if !pending.is_empty() { tx.send((Stream::Stdout, String::from_utf8_lossy(&pending).into_owned())).unwrap_or(()); }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::Schedule;
+
+    fn command(command: &str) -> ExecutableCommand {
+        ExecutableCommand::new("test".to_string(), command.to_string(), "/tmp".to_string(), Schedule::Every(Duration::from_secs(1)))
+    }
+
+    #[test]
+    fn captures_both_streams_and_exit_code() {
+        let (exit, output) = exec_command(&command("sh -c 'echo out; echo err >&2; exit 3'"), None, WindowSize::default()).unwrap();
+        assert_eq!(exit, Exit::Code(3));
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+    }
+
+    #[test]
+    fn feeds_stdin() {
+        let (_, output) = exec_command(&command("tr a-z A-Z"), Some("piped in\n"), WindowSize::default()).unwrap();
+        assert_eq!(output.stdout, "PIPED IN\n");
+    }
+
+    #[test]
+    fn streams_lines_as_they_are_written() {
+        let mut lines = vec![];
+        let exit = stream_command(&command("sh -c 'echo one; echo two >&2; exit 4'"), WindowSize::default, |stream, line| { lines.push((stream, line)); true }, || false).unwrap();
+        assert_eq!(exit, Exit::Code(4));
+        lines.sort_by_key(|(stream, _)| *stream == Stream::Stderr);
+        assert_eq!(lines, vec![(Stream::Stdout, "one\n".to_string()), (Stream::Stderr, "two\n".to_string())]);
+    }
+
+    #[test]
+    fn stopping_a_stream_kills_the_command() {
+        let started = Instant::now();
+        let exit = stream_command(&command("sleep 10"), WindowSize::default, |_, _| true, || started.elapsed() > Duration::from_millis(100)).unwrap();
+        assert_eq!(exit, Exit::Signal(libc::SIGTERM));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn runs_commands_in_a_terminal_of_the_given_size() {
+        let cmd = ExecutableCommand { pty: true, ..command("sh -c 'test -t 0 && test -t 1 && test -t 2 && stty size; echo $TERM; printf end'") };
+        let (exit, output) = exec_command(&cmd, None, WindowSize { rows: 12, cols: 34 }).unwrap();
+        assert_eq!(exit, Exit::Code(0));
+        assert_eq!(output.stdout, "12 34\nxterm-256color\nend");
+    }
+
+    #[test]
+    fn resizes_streaming_terminals() {
+        let started = Instant::now();
+        let window = || if started.elapsed() < Duration::from_millis(300) { WindowSize { rows: 10, cols: 20 } } else { WindowSize { rows: 30, cols: 40 } };
+        let cmd = ExecutableCommand { pty: true, ..command("sh -c 'stty size; sleep 1; stty size'") };
+        let mut lines = vec![];
+        stream_command(&cmd, window, |_, line| { lines.push(line); true }, || false).unwrap();
+        assert_eq!(lines, vec!["10 20\n", "30 40\n"]);
+    }
+
+    #[test]
+    fn splits_arguments_like_a_shell() {
+        assert_eq!(command_line("grep 'two words' \"a \\\"b\\\"\"", None).unwrap(), vec!["grep", "two words", "a \"b\""]);
+        assert!(command_line("echo 'unbalanced", None).is_err());
+        assert!(command_line("   ", None).is_err());
+    }
+
+    #[test]
+    fn shell_commands_are_passed_whole() {
+        assert_eq!(command_line("ps aux | grep foo", Some("bash")).unwrap(), vec!["bash", "-c", "ps aux | grep foo"]);
+    }
+
+    #[test]
+    fn runs_pipelines_through_a_shell() {
+        let cmd = ExecutableCommand { shell: Some("/bin/sh".to_string()), ..command("echo $((1 + 2)) | tr 3 x") };
+        let (_, output) = exec_command(&cmd, None, WindowSize::default()).unwrap();
+        assert_eq!(output.stdout, "x\n");
+    }
+
+    #[test]
+    fn resolves_bare_names_on_the_path_and_paths_from_the_working_dir() {
+        assert_eq!(resolve_program("date", "/opt"), "date");
+        assert_eq!(resolve_program("./run.sh", "/opt"), "/opt/./run.sh");
+        assert_eq!(resolve_program("/bin/date", "/opt"), "/bin/date");
+    }
+
+    #[test]
+    fn sets_the_environment() {
+        let mut env = BTreeMap::new();
+        env.insert("GREETING".to_string(), "hello".to_string());
+        let cmd = ExecutableCommand { env, clear_env: true, ..command("/usr/bin/env") };
+        let (_, output) = exec_command(&cmd, None, WindowSize::default()).unwrap();
+        assert_eq!(output.stdout, "GREETING=hello\n");
+    }
+
+    #[test]
+    fn kills_commands_which_outlive_their_timeout() {
+        let started = Instant::now();
+        let cmd = ExecutableCommand { timeout: Some(Duration::from_millis(200)), ..command("sleep 10") };
+        let (exit, _) = exec_command(&cmd, None, WindowSize::default()).unwrap();
+        assert_eq!(exit, Exit::TimedOut(Duration::from_millis(200)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use crate::TaskId;
+
+/***
+WindowSize: The size of the terminal a command thinks it's running in.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl Default for WindowSize {
+    fn default() -> Self { WindowSize { rows: 24, cols: 80 } }
+}
+
+impl WindowSize {
+    fn to_winsize(self) -> libc::winsize {
+        libc::winsize { ws_row: self.rows.max(1), ws_col: self.cols.max(1), ws_xpixel: 0, ws_ypixel: 0 }
+    }
+}
+
+/***
+WindowSizes: How big each task's view is, as last reported by the UI. Shared with the threads running
+    tasks, so each run starts at the current size. Tasks the UI hasn't sized yet get the default.
+ */
+#[derive(Clone, Default)]
+pub struct WindowSizes(Arc<Mutex<HashMap<TaskId, WindowSize>>>);
+
+impl WindowSizes {
+    pub fn get(&self, id: &str) -> WindowSize {
+        self.0.lock().map(|sizes| sizes.get(id).copied().unwrap_or_default()).unwrap_or_default()
+    }
+
+    pub fn set(&self, id: TaskId, size: WindowSize) {
+        if let Ok(mut sizes) = self.0.lock() { sizes.insert(id, size); }
+    }
+}
+
+/***
+Pty: A pseudo-terminal. Commands attached to it see a terminal on stdin, stdout and stderr,
+    so they keep their colours and interactive behaviour. We read what they write from `master`.
+ */
+pub struct Pty {
+    pub master: File,
+    slave: File,
+}
+
+impl Pty {
+    pub fn open(size: WindowSize) -> io::Result<Pty> {
+        let (mut master, mut slave) = (0, 0);
+        let winsize = size.to_winsize();
+        if unsafe { libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), &winsize) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let pty = unsafe { Pty { master: File::from_raw_fd(master), slave: File::from_raw_fd(slave) } };
+        // Commands started on other threads in the meantime would otherwise get copies of both ends, and hold the
+        // terminal open long after the command attached to it has finished with it
+        close_on_exec(&pty.master)?;
+        close_on_exec(&pty.slave)?;
+        pty.keep_newlines()?;
+        Ok(pty)
+    }
+
+    /// Terminals turn "\n" into "\r\n" on the way out by default. We're not a real terminal, so leave them be.
+    fn keep_newlines(&self) -> io::Result<()> {
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(self.slave.as_raw_fd(), &mut termios) != 0 { return Err(io::Error::last_os_error()); }
+            termios.c_oflag &= !libc::ONLCR;
+            if libc::tcsetattr(self.slave.as_raw_fd(), libc::TCSANOW, &termios) != 0 { return Err(io::Error::last_os_error()); }
+        }
+        Ok(())
+    }
+
+    /// Connect a command to the terminal. It gets a session of its own, with the pty as its controlling
+    /// terminal - which also makes it a process group leader, so it can be signalled along with its children.
+    pub fn attach(&self, command: &mut Command) -> io::Result<()> {
+        command.
+            stdin(Stdio::from(self.slave.try_clone()?)).
+            stdout(Stdio::from(self.slave.try_clone()?)).
+            stderr(Stdio::from(self.slave.try_clone()?));
+
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 { return Err(io::Error::last_os_error()); }
+                if libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 { return Err(io::Error::last_os_error()); }
+                Ok(())
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Hand the reading end over, closing ours of the writing end - so reads finish when the command does.
+    pub fn into_master(self) -> File {
+        self.master
+    }
+}
+
+fn close_on_exec(file: &File) -> io::Result<()> {
+    match unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(())
+    }
+}
+
+/// Tell everything running in a terminal that it's changed size.
+pub fn resize(master: &File, size: WindowSize) -> io::Result<()> {
+    match unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size.to_winsize()) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn commands_started_meanwhile_do_not_hold_the_terminal_open() {
+        let pty = Pty::open(WindowSize::default()).unwrap();
+        let mut bystander = Command::new("sleep").arg("5").spawn().unwrap();
+
+        let mut command = Command::new("printf");
+        command.arg("done");
+        pty.attach(&mut command).unwrap();
+        let mut child = command.spawn().unwrap();
+        drop(command);
+
+        let mut master = pty.into_master();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = vec![];
+            // The terminal reports an error rather than the end once nothing has it open - either will do
+            master.read_to_end(&mut output).unwrap_or(0);
+            tx.send(output).unwrap_or(());
+        });
+
+        let output = rx.recv_timeout(Duration::from_secs(3));
+        bystander.kill().unwrap_or(());
+        bystander.wait().unwrap();
+        child.wait().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.expect("the terminal was held open")), "done");
+    }
+}
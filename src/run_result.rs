@@ -0,0 +1,134 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::{Duration, SystemTime};
+
+use crate::tasks::OutputMode;
+use crate::TaskId;
+
+/***
+Exit: How a task's process finished.
+    Code(n)     -> Exited normally with status n
+    Signal(n)   -> Killed by signal n
+    TimedOut(d) -> Killed by us after running for longer than its timeout, d
+    Error(msg)  -> Never got going - e.g. the command doesn't exist
+    Skipped(id) -> Never started, because task id - which it runs after - didn't succeed
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Exit {
+    Code(i32),
+    Signal(i32),
+    TimedOut(Duration),
+    Error(String),
+    Skipped(TaskId),
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Exit::Code(code) => write!(f, "exited with {}", code),
+            Exit::Signal(sig) => write!(f, "killed by signal {}", sig),
+            Exit::TimedOut(timeout) => write!(f, "timed out after {:?}", timeout), // Ex: "1.5s" or "500ms"
+            Exit::Error(err) => write!(f, "couldn't run: {}", err),
+            Exit::Skipped(id) => write!(f, "skipped because '{}' didn't succeed", id),
+        }
+    }
+}
+
+/***
+RunResult: Everything we know about a single run of a task.
+    `interleaved` holds both streams' lines in the order we read them.
+ */
+#[derive(Clone, Debug)]
+pub struct RunResult {
+    pub id: TaskId,
+    pub exit: Exit,
+    pub stdout: String,
+    pub stderr: String,
+    pub interleaved: String,
+    pub started: SystemTime,
+    pub duration: Duration,
+}
+
+impl RunResult {
+    /// Stands in for a run which never happened because `failed` didn't succeed.
+    pub fn skipped(id: &str, failed: &str) -> RunResult {
+        RunResult {
+            id: id.to_string(),
+            exit: Exit::Skipped(failed.to_string()),
+            stdout: String::new(),
+            stderr: String::new(),
+            interleaved: String::new(),
+            started: SystemTime::now(),
+            duration: Duration::ZERO,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.exit == Exit::Code(0)
+    }
+
+    /// The text to display for this run, according to the task's output mode.
+    pub fn output(&self, mode: OutputMode) -> &str {
+        match mode {
+            OutputMode::Stdout => &self.stdout,
+            OutputMode::Stderr => &self.stderr,
+            OutputMode::Interleaved => &self.interleaved,
+            OutputMode::StderrOnFailure => if self.succeeded() || self.stderr.is_empty() { &self.stdout } else { &self.stderr },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(exit: Exit) -> RunResult {
+        RunResult {
+            id: "test".to_string(),
+            exit,
+            stdout: "out\n".to_string(),
+            stderr: "err\n".to_string(),
+            interleaved: "err\nout\n".to_string(),
+            started: SystemTime::now(),
+            duration: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn only_zero_exit_codes_succeed() {
+        assert!(result(Exit::Code(0)).succeeded());
+        assert!(!result(Exit::Code(1)).succeeded());
+        assert!(!result(Exit::Signal(9)).succeeded());
+        assert!(!result(Exit::TimedOut(Duration::from_secs(5))).succeeded());
+        assert!(!result(Exit::Error("nope".to_string())).succeeded());
+        assert!(!RunResult::skipped("test", "build").succeeded());
+    }
+
+    #[test]
+    fn timeouts_say_how_long_they_were() {
+        assert_eq!(Exit::TimedOut(Duration::from_secs(5)).to_string(), "timed out after 5s");
+        assert_eq!(Exit::TimedOut(Duration::from_millis(500)).to_string(), "timed out after 500ms");
+        assert_eq!(Exit::TimedOut(Duration::from_millis(1500)).to_string(), "timed out after 1.5s");
+    }
+
+    #[test]
+    fn stderr_on_failure_keeps_stdout_when_successful() {
+        assert_eq!(result(Exit::Code(0)).output(OutputMode::StderrOnFailure), "out\n");
+        assert_eq!(result(Exit::Code(2)).output(OutputMode::StderrOnFailure), "err\n");
+    }
+
+    #[test]
+    fn stderr_on_failure_falls_back_to_stdout_if_stderr_is_empty() {
+        let mut r = result(Exit::Code(2));
+        r.stderr = String::new();
+        assert_eq!(r.output(OutputMode::StderrOnFailure), "out\n");
+    }
+
+    #[test]
+    fn other_modes_pick_their_stream() {
+        let r = result(Exit::Code(0));
+        assert_eq!(r.output(OutputMode::Stdout), "out\n");
+        assert_eq!(r.output(OutputMode::Stderr), "err\n");
+        assert_eq!(r.output(OutputMode::Interleaved), "err\nout\n");
+    }
+}
@@ -1,41 +1,102 @@
+use std::cmp::min;
 use std::collections::HashMap;
-use std::process::{Command, Output};
-use std::str;
-use std::sync::mpsc::{Sender, Receiver};
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::mpsc::{Sender, Receiver, RecvTimeoutError, TryRecvError};
 use std::thread;
-use std::thread::{JoinHandle, sleep};
+use std::thread::JoinHandle;
 
+use crate::dependencies::{Pipeline, Step};
+use crate::events::Event;
 use crate::executable_command::ExecutableCommand;
-use crate::tasks::Task;
-use std::time::{SystemTime, Duration};
+use crate::file_watcher::watch_files;
+use crate::process::{exec_command, stream_command, CapturedOutput};
+use crate::pty::{WindowSize, WindowSizes};
+use crate::run_result::{Exit, RunResult};
+use crate::schedule::{parse_duration, Clock, Schedule, SystemClock};
+use crate::tasks::{Shell, Task, TaskMode, Trigger};
+use crate::TaskId;
+use std::time::{SystemTime, Duration, Instant};
 use log::{trace, info, warn};
+use notify::RecommendedWatcher;
+
+/***
+RunnerCommand: Requests sent to the TaskRunner from the UI.
+    Run    -> Run a task once. The first word is the task id, anything else is appended to its command.
+              This is the only way manual tasks ever run. Streaming tasks are restarted instead.
+    Reload -> Replace the task list, restarting only the tasks which changed.
+    Finished -> A run has completed. Sent by the runner's own threads, so it can start whatever runs after it.
+    Resize -> A task's view changed size. Tasks run in a pty use it as their terminal size.
+    Shutdown -> Stop every task, waiting for streaming commands (which would otherwise outlive us) to be killed.
+ */
+pub enum RunnerCommand {
+    Run(String),
+    Reload(Vec<Task>),
+    Finished(RunResult),
+    Resize(TaskId, WindowSize),
+    Shutdown,
+}
+
+// Builds and saves tend to touch several files at once. Wait for things to settle before running.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+// How long to wait before restarting a streaming command which exited. Doubles each time it exits quickly.
+const MIN_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+// A streaming command which ran for this long was working, so starts again from the shortest backoff
+const STABLE_STREAM_TIME: Duration = Duration::from_secs(30);
+
+/***
+TaskLoop: Handle to a task's polling thread. Dropping it stops the thread after its current run.
+    The thread wakes early whenever its watcher sees a watched file change. The watcher holds the only
+    other sender for the thread's wake-up channel, so dropping both disconnects it.
+ */
+struct TaskLoop {
+    _stop: Sender<()>,
+    _watcher: Option<RecommendedWatcher>,
+    stream: Option<JoinHandle<()>>,
+}
+
+/***
+Wake: Why a task loop stopped waiting.
+ */
+enum Wake {
+    Timer,
+    FilesChanged,
+    Stopped,
+}
 
 pub struct TaskRunner {
     pub commands: Vec<ExecutableCommand>,
-    system_command_sender: Sender<HashMap<String, String>>,
-    run_task_receiver: Receiver<String>,
-    running: bool,
+    loops: HashMap<TaskId, TaskLoop>,
+    pipeline: Pipeline,
+    window_sizes: WindowSizes,
+    event_sender: Sender<Event>,
+    run_task_sender: Sender<RunnerCommand>,
+    run_task_receiver: Receiver<RunnerCommand>,
 }
 
 impl TaskRunner {
+    /// `run_task_sender` must feed `run_task_receiver` - finished runs are reported back through it.
     pub fn new(tasks: Vec<Task>,
-               system_command_sender: Sender<HashMap<String, String>>,
-               run_task_receiver: Receiver<String>) -> TaskRunner {
-        let commands = tasks.iter().
-            map(|t| task_to_command(t)).
+               event_sender: Sender<Event>,
+               run_task_sender: Sender<RunnerCommand>,
+               run_task_receiver: Receiver<RunnerCommand>) -> TaskRunner {
+        let commands: Vec<ExecutableCommand> = tasks.iter().
+            map(task_to_command).
             collect();
+        let pipeline = Pipeline::new(&commands);
 
-        TaskRunner { commands, system_command_sender, run_task_receiver, running: true }
+        TaskRunner { commands, loops: HashMap::new(), pipeline, window_sizes: WindowSizes::default(), event_sender, run_task_sender, run_task_receiver }
     }
 
     pub fn run(&mut self) {
-        let handles: Vec<JoinHandle<()>> = self.commands.iter().map(|cmd|
-            self.run_task_loop(&cmd)
-        ).collect();
+        for cmd in self.commands.iter().filter(|cmd| needs_loop(cmd)) {
+            self.loops.insert(cmd.id.clone(), self.start_loop(cmd, None));
+        }
 
-        while self.running {
-            match self.run_task_receiver.recv() {
-                Ok(command) => {
+        while let Ok(command) = self.run_task_receiver.recv() {
+            match command {
+                RunnerCommand::Run(command) => {
                     let task_id = command.
                         split_whitespace().
                         next().
@@ -43,98 +104,284 @@ impl TaskRunner {
                         to_string();
 
                     self.run_command(task_id, command);
-                }
-                Err(_) => {}
+                },
+                RunnerCommand::Reload(tasks) => self.reload(tasks),
+                RunnerCommand::Finished(result) => self.finished(result),
+                RunnerCommand::Resize(id, size) => self.window_sizes.set(id, size),
+                RunnerCommand::Shutdown => return self.shutdown()
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        info!("Stopping all tasks");
+        let streams: Vec<JoinHandle<()>> = self.loops.drain().filter_map(|(_, task_loop)| task_loop.stream).collect();
+        for stream in streams {
+            stream.join().unwrap_or(());
+        }
+    }
+
+    /// Stop a task's loop, handing back its stream thread (if it has one) as it may take a while to wind down.
+    /// Anything taking the task's place should wait for it, rather than the runner.
+    fn stop_loop(&mut self, id: &str) -> Option<JoinHandle<()>> {
+        // The rest of the loop is dropped, which tells the stream thread to stop
+        self.loops.remove(id).and_then(|task_loop| task_loop.stream)
+    }
+
+    pub fn reload(&mut self, tasks: Vec<Task>) {
+        let commands: Vec<ExecutableCommand> = tasks.iter().map(task_to_command).collect();
+
+        let changed: Vec<TaskId> = self.commands.iter().filter(|old| !commands.contains(old)).map(|old| old.id.clone()).collect();
+        let mut stopping = HashMap::new();
+        for id in changed {
+            info!("Stopping {}", id);
+            if let Some(stream) = self.stop_loop(&id) { stopping.insert(id, stream); }
+        }
+
+        for cmd in commands.iter().filter(|cmd| needs_loop(cmd)) {
+            if !self.commands.contains(cmd) {
+                info!("Starting {}", cmd.id);
+                self.loops.insert(cmd.id.clone(), self.start_loop(cmd, stopping.remove(&cmd.id)));
             }
         }
 
-        for h in handles { h.join().unwrap_or_else(|_| {}); }
+        self.pipeline.update(&commands);
+        self.commands = commands;
     }
 
-    pub fn run_command(&self, task_id: String, command: String) {
+    /// Show a run's output, then run (or skip) whatever was waiting on it.
+    fn finished(&mut self, result: RunResult) {
+        if self.event_sender.send(Event::TaskOutput(result.clone())).is_err() { return; }
+
+        for step in self.pipeline.finished(result) {
+            match step {
+                Step::Run(id) => match self.commands.iter().find(|cmd| cmd.id == id) {
+                    Some(cmd) => {
+                        info!("Running {} after its dependencies", id);
+                        self.run_task_once(cmd);
+                    },
+                    None => warn!("Could not find command '{}'", id)
+                },
+                Step::Skip { id, failed } => {
+                    info!("Skipping {} because {} didn't succeed", id, failed);
+                    self.event_sender.send(Event::TaskOutput(RunResult::skipped(&id, &failed))).unwrap_or(());
+                }
+            }
+        }
+    }
+
+    pub fn run_command(&mut self, task_id: String, command: String) {
         match self.commands.iter().find(|cmd| cmd.id == task_id) {
+            Some(cmd) if cmd.streaming => {
+                info!("Restarting {}", cmd.id);
+                let cmd = cmd.clone();
+                // Otherwise both would be running for a while, their lines mixed up in the one view
+                let previous = self.stop_loop(&task_id);
+                let restarted = self.run_stream_loop(&cmd, previous);
+                self.loops.insert(task_id, restarted);
+            },
             Some(cmd) => {
                 let mut mutcmd = cmd.clone();
-                let mut parts = command.split_whitespace();
-                parts.next(); // pop the initial command off
-                mutcmd.command += " ";
-                mutcmd.command += parts.collect::<Vec<&str>>().join(" ").as_str();
+                // pop the initial command off, keeping the rest as typed so quoting survives
+                if let Some((_, args)) = command.trim().split_once(char::is_whitespace) {
+                    mutcmd.command += " ";
+                    mutcmd.command += args;
+                }
 
+                info!("Running manual '{}' command", mutcmd.id);
                 self.run_task_once(&mutcmd);
             }
             None => { warn!("Could not find command '{}'", task_id) }
         }
     }
 
-    fn run_task_loop(&self, command: &ExecutableCommand) -> JoinHandle<()> {
-        let trx = self.system_command_sender.clone();
+    /// Start a task's loop, once the stream thread of the loop it replaces (if any) has finished.
+    fn start_loop(&self, command: &ExecutableCommand, previous: Option<JoinHandle<()>>) -> TaskLoop {
+        match command.streaming {
+            true => self.run_stream_loop(command, previous),
+            false => self.run_task_loop(command, previous)
+        }
+    }
+
+    fn run_task_loop(&self, command: &ExecutableCommand, previous: Option<JoinHandle<()>>) -> TaskLoop {
+        let trx = self.run_task_sender.clone();
         let cmd = command.clone();
+        let sizes = self.window_sizes.clone();
+        let outputs = self.pipeline.outputs();
+        let (stop_tx, wake_rx) = mpsc::channel::<()>();
         info!("spawn {} thread", cmd.id);
 
+        let watcher = match cmd.watch.is_empty() {
+            true => None,
+            false => watch_files(&cmd.watch, &cmd.working_dir, stop_tx.clone()).
+                map_err(|err| warn!("Can't watch files for {}: {}", cmd.id, err)).
+                ok()
+        };
+
         thread::Builder::new().name(cmd.id.clone()).spawn(move ||
             {
+                if let Some(previous) = previous { previous.join().unwrap_or(()); }
                 loop {
-                    let last_run = SystemTime::now();
+                    let last_run = SystemClock.now();
+                    let started = Instant::now();
+
+                    let stdin = cmd.stdin_from.as_deref().and_then(|id| outputs.get(id));
+                    let output = run(&cmd, stdin.as_deref(), sizes.get(&cmd.id));
+
+                    // If we were stopped while running, this output is stale.
+                    let changed_while_running = match wake_rx.try_recv() {
+                        Err(TryRecvError::Disconnected) => break,
+                        Err(TryRecvError::Empty) => false,
+                        Ok(()) => true
+                    };
 
-                    let mut h = HashMap::new();
-                    h.insert(cmd.id.clone(), convert_output(exec_command(cmd.command.clone(), cmd.working_dir.clone())));
-                    trx.send(h).unwrap();
+                    if trx.send(RunnerCommand::Finished(output)).is_err() { break; }
 
-                    let nap_millis = cmd.millis_until_next_run(last_run.elapsed().unwrap().as_millis() as u64);
-                    let naptime = Duration::from_millis(nap_millis);
-                    info!("{} ran for {:.2?}", cmd.id, last_run.elapsed().unwrap());
+                    let nap_millis = cmd.millis_until_next_run(last_run, &SystemClock);
+                    info!("{} ran for {:.2?}", cmd.id, started.elapsed());
                     trace!("{} sleeping for {}ms", cmd.id, nap_millis);
-                    sleep(naptime);
+                    let wake = match changed_while_running {
+                        true => settle(&wake_rx),
+                        false => wait(&wake_rx, Duration::from_millis(nap_millis))
+                    };
+
+                    match wake {
+                        Wake::Timer => {},
+                        Wake::FilesChanged => info!("{} watched files changed", cmd.id),
+                        Wake::Stopped => break
+                    }
+                }
+                info!("{} thread stopped", cmd.id);
+            }).unwrap();
+
+        TaskLoop { _stop: stop_tx, _watcher: watcher, stream: None }
+    }
+
+    /// Keep a streaming task's command running, passing its output on line by line. If it exits, start it
+    /// again - waiting longer each time it fails quickly, so a broken command doesn't spin.
+    fn run_stream_loop(&self, command: &ExecutableCommand, previous: Option<JoinHandle<()>>) -> TaskLoop {
+        let trx = self.event_sender.clone();
+        let cmd = command.clone();
+        let sizes = self.window_sizes.clone();
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        info!("spawn {} stream thread", cmd.id);
+
+        let stream = thread::Builder::new().name(cmd.id.clone()).spawn(move || {
+            if let Some(previous) = previous { previous.join().unwrap_or(()); }
+            let stopped = || matches!(stop_rx.try_recv(), Err(TryRecvError::Disconnected));
+            let mut backoff = MIN_RESTART_BACKOFF;
+
+            loop {
+                let started = Instant::now();
+                let exit = stream_command(&cmd,
+                                          || sizes.get(&cmd.id),
+                                          |stream, line| trx.send(Event::TaskLine(cmd.id.clone(), stream, line)).is_ok(),
+                                          stopped).
+                    unwrap_or_else(|err| Exit::Error(err.to_string()));
+
+                if stopped() { break; }
+
+                if started.elapsed() >= STABLE_STREAM_TIME { backoff = MIN_RESTART_BACKOFF; }
+                info!("{} {} - restarting in {:?}", cmd.id, exit, backoff);
+                if trx.send(Event::StreamEnded { id: cmd.id.clone(), exit, restart_in: backoff }).is_err() { break; }
+
+                match stop_rx.recv_timeout(backoff) {
+                    Err(RecvTimeoutError::Timeout) => {},
+                    _ => break
                 }
-            }).unwrap()
+                backoff = min(backoff * 2, MAX_RESTART_BACKOFF);
+            }
+            info!("{} stream thread stopped", cmd.id);
+        }).unwrap();
+
+        TaskLoop { _stop: stop_tx, _watcher: None, stream: Some(stream) }
     }
 
-    fn run_task_once(&self, command: &ExecutableCommand) -> () {
-        let trx = self.system_command_sender.clone();
+    /// Run a task in the background, outside its loop. Tasks reading another's output get its latest.
+    fn run_task_once(&self, command: &ExecutableCommand) {
+        let trx = self.run_task_sender.clone();
         let cmd = command.clone();
-        info!("Running manual '{}' command", cmd.id);
+        let window = self.window_sizes.get(&cmd.id);
+        let stdin = cmd.stdin_from.as_deref().and_then(|id| self.pipeline.outputs().get(id));
 
-        let mut h = HashMap::new();
-        h.insert(cmd.id.clone(), convert_output(exec_command(cmd.command.clone(), cmd.working_dir.clone())));
-        trx.send(h).unwrap();
+        thread::Builder::new().name(format!("{}-once", cmd.id)).spawn(move || {
+            trx.send(RunnerCommand::Finished(run(&cmd, stdin.as_deref(), window))).unwrap_or(());
+        }).unwrap();
     }
 }
 
-fn convert_output(output: Output) -> String {
-    let std_text = match str::from_utf8(&output.stdout) {
-        Ok(t) => t.to_owned(),
-        Err(_) => String::from("")
-    };
+/// Manual tasks don't get a loop - they only run when asked to.
+fn needs_loop(cmd: &ExecutableCommand) -> bool {
+    cmd.streaming || cmd.schedule != Schedule::Never || !cmd.watch.is_empty()
+}
 
-    let err_text = match str::from_utf8(&output.stderr) {
-        Ok(t) => t.to_owned(),
-        Err(_) => String::from("")
-    };
+/// Sleep until the next scheduled run, unless a watched file changes or we're stopped first.
+fn wait(wake: &Receiver<()>, naptime: Duration) -> Wake {
+    match wake.recv_timeout(naptime) {
+        Ok(()) => settle(wake),
+        Err(RecvTimeoutError::Timeout) => Wake::Timer,
+        Err(RecvTimeoutError::Disconnected) => Wake::Stopped
+    }
+}
 
-    // If we got an error, show that.
-    match err_text.len() {
-        0 => std_text,
-        _ => err_text
+/// Files are changing - swallow the rest of the burst before running.
+fn settle(wake: &Receiver<()>) -> Wake {
+    loop {
+        match wake.recv_timeout(SETTLE_TIME) {
+            Ok(()) => {},
+            Err(RecvTimeoutError::Timeout) => return Wake::FilesChanged,
+            Err(RecvTimeoutError::Disconnected) => return Wake::Stopped
+        }
     }
 }
 
-fn exec_command(command: String, working_dir: String) -> Output {
-    let mut parts = command.trim().split_whitespace();
-    let cmd = parts.next().unwrap();
-    let args = parts;
+fn run(cmd: &ExecutableCommand, stdin: Option<&str>, window: WindowSize) -> RunResult {
+    let started = SystemTime::now();
+    let (exit, output) = match exec_command(cmd, stdin, window) {
+        Ok(ran) => ran,
+        Err(err) => {
+            warn!("Couldn't run {}: {}", cmd.id, err);
+            (Exit::Error(err.to_string()), CapturedOutput::default())
+        }
+    };
 
-    info!("Running {}/{} {}", working_dir, cmd, args.clone().map(|s| s.to_string()).collect::<Vec<String>>().join(" "));
+    RunResult {
+        id: cmd.id.clone(),
+        exit,
+        stdout: output.stdout,
+        stderr: output.stderr,
+        interleaved: output.interleaved,
+        started,
+        duration: started.elapsed().unwrap_or_default(),
+    }
+}
 
-    Command::new(vec!(working_dir.clone(), cmd.to_string()).join("/"))
-        .current_dir(working_dir.clone())
-        .args(args)
-        .output()
-        .expect("failed to execute process")
+/// How a task's run - the runner restarts a task on reload only if this has changed.
+pub fn task_to_command(t: &Task) -> ExecutableCommand {
+    ExecutableCommand {
+        timeout: t.timeout.as_deref().and_then(parse_duration),
+        shell: t.shell.as_ref().and_then(Shell::program),
+        env: t.env.clone(),
+        env_file: t.env_file.as_ref().map(|f| Path::new(&t.path).join(f)),
+        clear_env: t.clear_env,
+        watch: if t.trigger == Trigger::Manual { vec![] } else { t.watch.clone() },
+        after: t.after.clone(),
+        stdin_from: t.stdin_from.clone(),
+        streaming: t.mode == TaskMode::Stream,
+        pty: t.pty,
+        ..ExecutableCommand::new(t.id.clone(),
+                                 t.command.clone(),
+                                 t.path.clone(),
+                                 task_schedule(t))
+    }
 }
 
-fn task_to_command(t: &Task) -> ExecutableCommand {
-    ExecutableCommand::new(t.id.clone(),
-                           t.command.clone(),
-                           t.path.clone(),
-                           t.period.clone())
+fn task_schedule(t: &Task) -> Schedule {
+    let schedule = match (t.trigger, &t.period, &t.schedule) {
+        (Trigger::Manual, _, _) | (_, None, None) => Ok(Schedule::Never),
+        (_, Some(period), _) => parse_duration(period).map(Schedule::Every).ok_or_else(|| format!("bad period '{}'", period)),
+        (_, None, Some(schedule)) => Schedule::parse(schedule),
+    };
+
+    schedule.unwrap_or_else(|err| panic!("Couldn't work out when to run '{}': {}", t.id, err))
 }
@@ -0,0 +1,361 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use regex::Regex;
+
+// Give up looking for the next matching time after this many years. Catches things like "0 0 30 2 *".
+const MAX_YEARS_AHEAD: i32 = 5;
+
+/***
+Clock: Where schedules get the current time from, so tests can control it.
+ */
+pub trait Clock {
+    fn now(&self) -> DateTime<Local>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> { Local::now() }
+}
+
+#[cfg(test)]
+pub struct FakeClock {
+    now: std::cell::Cell<DateTime<Local>>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> FakeClock {
+        FakeClock { now: std::cell::Cell::new(Local.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + chrono::Duration::from_std(by).unwrap());
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Local> { self.now.get() }
+}
+
+/***
+Schedule: When a task should run.
+    Every(d) -> Repeatedly, d after the start of the previous run
+    Cron(c)  -> Whenever the local time matches a cron expression
+    Never    -> No timer - only when triggered, from the console or by a file changing
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(CronSchedule),
+    Never,
+}
+
+impl Schedule {
+    /// Parse a `schedule` setting: a cron expression, a shorthand like "@daily", or a duration.
+    pub fn parse(schedule: &str) -> Result<Schedule, String> {
+        if let Some(every) = parse_duration(schedule) {
+            if every.is_zero() { return Err("it would run back to back, forever".to_string()); }
+            return Ok(Schedule::Every(every));
+        }
+
+        CronSchedule::parse(schedule).map(Schedule::Cron)
+    }
+
+    /// How long after `now` to wait before the next run, given the last run started at `last_run`.
+    pub fn time_until_next_run(&self, last_run: DateTime<Local>, now: DateTime<Local>) -> Duration {
+        match self {
+            Schedule::Every(period) => {
+                let elapsed = (now - last_run).to_std().unwrap_or_default();
+                period.checked_sub(elapsed).unwrap_or_default()
+            },
+            Schedule::Cron(cron) => match cron.next_after(now) {
+                Some(next) => (next - now).to_std().unwrap_or_default(),
+                None => Duration::MAX // Never matches, so never runs again
+            },
+            Schedule::Never => Duration::MAX
+        }
+    }
+}
+
+/***
+parse_duration: Converts durations like "10m", "1h30m", "500ms" or "2d" into a Duration.
+    A bare number is taken as seconds. Returns None for anything else.
+ */
+pub fn parse_duration(duration: &str) -> Option<Duration> {
+    let duration = duration.trim();
+
+    if let Ok(secs) = duration.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let whole = Regex::new(r"^(\d+(ms|s|m|h|d))+$").unwrap();
+    if !whole.is_match(duration) { return None; }
+
+    let parts = Regex::new(r"(\d+)(ms|s|m|h|d)").unwrap();
+    let total = parts.captures_iter(duration).try_fold(Duration::ZERO, |total, c| {
+        let n = c[1].parse::<u64>().ok()?;
+        let millis = match &c[2] {
+            "ms" => Some(n),
+            "s" => n.checked_mul(1000),
+            "m" => n.checked_mul(60_000),
+            "h" => n.checked_mul(3_600_000),
+            _ => n.checked_mul(86_400_000), // "d"
+        }?;
+        total.checked_add(Duration::from_millis(millis))
+    });
+    total
+}
+
+/***
+CronSchedule: A standard five field cron expression - minute, hour, day of month, month, day of week.
+    Each field may be '*', a number, a range (1-5), either of those with a step (0-30/10) or a comma separated list
+    of those. Months and weekdays may also be given by name (jan, mon). Times are local.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Cron's quirk: if both day fields are restricted, a day matching *either* will do.
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+const MONTH_NAMES: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<CronSchedule, String> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            e if e.starts_with('@') => return Err(format!("unknown shorthand '{}'", e)),
+            e => e
+        };
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 fields (minute hour day-of-month month day-of-week), found {}", fields.len()));
+        }
+
+        // Sunday is both 0 and 7, so fold 7 back onto 0
+        let days_of_week = parse_field(fields[4], 0, 7, &DAY_NAMES, 0)?;
+        let days_of_week = (days_of_week | (days_of_week >> 7)) & 0x7f;
+
+        Ok(CronSchedule {
+            minutes: parse_field(fields[0], 0, 59, &[], 0)?,
+            hours: parse_field(fields[1], 0, 23, &[], 0)?,
+            days_of_month: parse_field(fields[2], 1, 31, &[], 1)?,
+            months: parse_field(fields[3], 1, 12, &MONTH_NAMES, 1)?,
+            days_of_week,
+            days_of_month_restricted: !fields[2].starts_with('*'),
+            days_of_week_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    /// The first matching minute strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+        let give_up_after = start.year() + MAX_YEARS_AHEAD;
+        let mut t = start;
+
+        while t.year() <= give_up_after {
+            if !bit(self.months, t.month()) {
+                t = start_of_next_month(t)?;
+            } else if !self.day_matches(&t) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !bit(self.hours, t.hour()) {
+                t = t.with_minute(0)? + chrono::Duration::hours(1);
+            } else if !bit(self.minutes, t.minute()) {
+                t += chrono::Duration::minutes(1);
+            } else {
+                match Local.from_local_datetime(&t).earliest() {
+                    Some(next) => return Some(next),
+                    None => t += chrono::Duration::minutes(1) // Skipped by a daylight savings change
+                }
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, t: &NaiveDateTime) -> bool {
+        let dom = bit(self.days_of_month, t.day());
+        let dow = bit(self.days_of_week, t.weekday().num_days_from_sunday());
+
+        match (self.days_of_month_restricted, self.days_of_week_restricted) {
+            (true, true) => dom || dow,
+            _ => dom && dow
+        }
+    }
+}
+
+fn bit(set: u64, n: u32) -> bool {
+    set & (1 << n) != 0
+}
+
+fn start_of_next_month(t: NaiveDateTime) -> Option<NaiveDateTime> {
+    let (year, month) = if t.month() == 12 { (t.year() + 1, 1) } else { (t.year(), t.month() + 1) };
+    NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+/***
+parse_field: Turn one cron field into a bitset of the values it matches.
+    `names` (if any) are accepted in place of numbers, the first name meaning `first_name_value`.
+ */
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], first_name_value: u32) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let lower = s.to_lowercase();
+        let n = match names.iter().position(|name| *name == lower) {
+            Some(i) => i as u32 + first_name_value,
+            None => s.parse::<u32>().map_err(|_| format!("'{}' isn't a number", s))?
+        };
+        if n < min || n > max { return Err(format!("{} is outside {}-{}", n, min, max)); }
+        Ok(n)
+    };
+
+    let mut set = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(|| format!("bad step '{}'", step))?),
+            None => (item, 1)
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            r => match r.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                // "5/10" means every 10 from 5 onwards
+                None => { let n = value(r)?; (n, if item.contains('/') { max } else { n }) }
+            }
+        };
+
+        if start > end { return Err(format!("range {}-{} is backwards", start, end)); }
+        for n in (start..=end).step_by(step as usize) { set |= 1 << n; }
+    }
+
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()
+    }
+
+    fn cron(expression: &str) -> CronSchedule {
+        CronSchedule::parse(expression).unwrap()
+    }
+
+    #[test]
+    fn parses_simple_and_compound_durations() {
+        assert_eq!(parse_duration("12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_duration("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_duration("1m"), Some(Duration::from_secs(60)));
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("2d"), Some(Duration::from_secs(2 * 86400)));
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration(" 1m30s "), Some(Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn rejects_bad_durations() {
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("10 minutes"), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn schedules_are_durations_or_cron() {
+        assert_eq!(Schedule::parse("90s"), Ok(Schedule::Every(Duration::from_secs(90))));
+        assert_eq!(Schedule::parse("@hourly"), Ok(Schedule::Cron(cron("0 * * * *"))));
+        assert!(Schedule::parse("every tuesday").is_err());
+        assert!(Schedule::parse("0s").is_err());
+        assert!(Schedule::parse("@fortnightly").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_cron_fields() {
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("5-1 * * * *").is_err());
+        assert!(CronSchedule::parse("* * * smarch *").is_err());
+    }
+
+    #[test]
+    fn fixed_periods_wait_out_the_rest_of_the_period() {
+        let clock = FakeClock::at(2026, 3, 2, 12, 0, 0);
+        let every = Schedule::Every(Duration::from_secs(10));
+        let last_run = clock.now();
+
+        clock.advance(Duration::from_millis(2500));
+        assert_eq!(every.time_until_next_run(last_run, clock.now()), Duration::from_millis(7500));
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(every.time_until_next_run(last_run, clock.now()), Duration::ZERO);
+    }
+
+    #[test]
+    fn weekday_mornings() {
+        let weekdays_at_nine = cron("0 9 * * mon-fri");
+        // Friday 2026-03-06, after 9 -> Monday
+        assert_eq!(weekdays_at_nine.next_after(local(2026, 3, 6, 9, 0, 0)), Some(local(2026, 3, 9, 9, 0, 0)));
+        // Monday before 9 -> that morning
+        assert_eq!(weekdays_at_nine.next_after(local(2026, 3, 9, 8, 59, 30)), Some(local(2026, 3, 9, 9, 0, 0)));
+    }
+
+    #[test]
+    fn on_the_hour_and_half_hour() {
+        let half_hourly = cron("0,30 * * * *");
+        assert_eq!(half_hourly.next_after(local(2026, 3, 2, 10, 0, 0)), Some(local(2026, 3, 2, 10, 30, 0)));
+        assert_eq!(half_hourly.next_after(local(2026, 3, 2, 10, 45, 10)), Some(local(2026, 3, 2, 11, 0, 0)));
+        assert_eq!(cron("*/15 * * * *").next_after(local(2026, 3, 2, 23, 59, 0)), Some(local(2026, 3, 3, 0, 0, 0)));
+    }
+
+    #[test]
+    fn calendar_shorthands() {
+        assert_eq!(cron("@daily").next_after(local(2026, 3, 2, 10, 0, 0)), Some(local(2026, 3, 3, 0, 0, 0)));
+        assert_eq!(cron("@monthly").next_after(local(2026, 12, 15, 10, 0, 0)), Some(local(2027, 1, 1, 0, 0, 0)));
+        assert_eq!(cron("@weekly").next_after(local(2026, 3, 2, 10, 0, 0)), Some(local(2026, 3, 8, 0, 0, 0)));
+    }
+
+    #[test]
+    fn either_day_field_matches_when_both_are_restricted() {
+        // The 13th, or any Friday
+        let spooky = cron("0 0 13 * fri");
+        assert_eq!(spooky.next_after(local(2026, 3, 2, 0, 0, 0)), Some(local(2026, 3, 6, 0, 0, 0)));
+        assert_eq!(spooky.next_after(local(2026, 3, 10, 0, 0, 0)), Some(local(2026, 3, 13, 0, 0, 0)));
+    }
+
+    #[test]
+    fn sunday_is_seven_too() {
+        assert_eq!(cron("0 12 * * 7"), cron("0 12 * * 0"));
+    }
+
+    #[test]
+    fn impossible_dates_never_come() {
+        assert_eq!(cron("0 0 30 2 *").next_after(local(2026, 1, 1, 0, 0, 0)), None);
+        let never = Schedule::Cron(cron("0 0 30 2 *"));
+        assert_eq!(never.time_until_next_run(local(2026, 1, 1, 0, 0, 0), local(2026, 1, 1, 0, 0, 0)), Duration::MAX);
+    }
+
+    #[test]
+    fn cron_waits_until_the_next_match() {
+        let clock = FakeClock::at(2026, 3, 2, 9, 59, 15);
+        let hourly = Schedule::parse("@hourly").unwrap();
+        assert_eq!(hourly.time_until_next_run(clock.now(), clock.now()), Duration::from_secs(45));
+    }
+}
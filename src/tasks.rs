@@ -1,51 +1,260 @@
-use std::fs::File;
-use std::io::Read;
+use std::collections::BTreeMap;
+use std::fs;
 use std::fmt;
+use std::fmt::Formatter;
+use std::path::Path;
 use serde::Deserialize;
 use std::ops::Deref;
-use serde::export::Formatter;
+use crate::dependencies;
+use crate::validation::{self, ConfigError, ConfigErrorKind};
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Debug)]
 pub struct Config {
+    #[serde(default)]
+    pub defaults: Defaults,
     pub tasks: Vec<Task>,
     pub layout: Layout,
 }
 
-#[derive(Deserialize, Clone)]
+/***
+Defaults: Settings shared by every task.
+    env -> Environment variables for every task. A task's own env takes precedence.
+ */
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Defaults {
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
 pub struct Task {
     pub id: String,
+    #[allow(dead_code)]
     pub name: String,
+    #[allow(dead_code)]
     pub description: String,
     pub path: String,
     pub command: String,
-    pub period: String,
+    pub period: Option<String>,
+    pub schedule: Option<String>,
+    #[serde(default)]
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub watch: Vec<String>,
+    #[serde(default)]
+    pub after: Vec<String>,
+    pub on_success: Option<String>,
+    pub stdin_from: Option<String>,
+    pub timeout: Option<String>,
+    #[serde(default)]
+    pub output: OutputMode,
+    #[serde(default)]
+    pub mode: TaskMode,
+    pub scrollback: Option<usize>,
+    #[serde(default)]
+    pub pty: bool,
+    pub shell: Option<Shell>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub env_file: Option<String>,
+    #[serde(default)]
+    pub clear_env: bool,
+}
+
+/***
+Shell: Whether to run a task's command through a shell, and which one.
+    shell = true      -> Run it with /bin/sh
+    shell = "bash"    -> Run it with the named shell
+    shell = false     -> Run it directly (the default)
+ */
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Shell {
+    Enabled(bool),
+    Program(String),
+}
+
+pub const DEFAULT_SHELL: &str = "/bin/sh";
+
+impl Shell {
+    pub fn program(&self) -> Option<String> {
+        match self {
+            Shell::Enabled(true) => Some(DEFAULT_SHELL.to_string()),
+            Shell::Enabled(false) => None,
+            Shell::Program(program) => Some(program.clone()),
+        }
+    }
+}
+
+/***
+Trigger: What makes a task run.
+    Schedule -> Its period or schedule, and changes to any files it watches. The default.
+    Manual   -> Only the console, or the tasks it runs after. Any period, schedule or watch is ignored.
+ */
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    #[default]
+    Schedule,
+    Manual,
+}
+
+impl Task {
+    /// Manual tasks never run on their own - only from the console, or after the tasks they depend on.
+    pub fn is_manual(&self) -> bool {
+        self.mode == TaskMode::Run &&
+            (self.trigger == Trigger::Manual || (self.period.is_none() && self.schedule.is_none() && self.watch.is_empty()))
+    }
+
+    /// How many lines of output a streaming task keeps.
+    pub fn scrollback(&self) -> usize {
+        self.scrollback.unwrap_or(DEFAULT_SCROLLBACK)
+    }
 }
 
-#[derive(Deserialize, Clone)]
+/***
+TaskMode: How a task's command is run.
+    Run    -> To completion each time the task is triggered, showing everything it wrote. The default.
+    Stream -> Continuously, showing each line as it arrives. Restarted if it exits.
+ */
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskMode {
+    #[default]
+    Run,
+    Stream,
+}
+
+// How many lines a streaming task keeps, unless it says otherwise
+pub const DEFAULT_SCROLLBACK: usize = 1000;
+
+/***
+OutputMode: Which of a task's output streams to display.
+    StderrOnFailure -> stdout, unless the command failed and wrote to stderr. The default.
+    Interleaved     -> Both, line by line in the order they were written.
+ */
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputMode {
+    Stdout,
+    Stderr,
+    Interleaved,
+    #[default]
+    StderrOnFailure,
+}
+
+/***
+Size: How wide or tall a view is.
+    width = 30      -> 30 characters
+    width = "30%"   -> 30% of the view it's in
+    width = "2fr"   -> Two shares of the space left in the view it's in, once the views beside it have taken
+                       what they need
+    width = "fill"  -> The same as "1fr"
+    width = "auto"  -> Whatever its content needs - the same as not giving one. Mostly for a grid's tracks.
+ */
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Size {
+    Chars(usize),
+    Relative(String),
+}
+
+/***
+Extent: What a Size means, once it's been read.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Extent {
+    Chars(usize),
+    Percent(usize),
+    Share(usize),
+    Auto,
+}
+
+impl Size {
+    /// None if it doesn't make sense - Ex: "150%" or "big".
+    pub fn extent(&self) -> Option<Extent> {
+        match self {
+            Size::Chars(n) => Some(Extent::Chars(*n)),
+            Size::Relative(s) if s == "fill" => Some(Extent::Share(1)),
+            Size::Relative(s) if s == "auto" => Some(Extent::Auto),
+            Size::Relative(s) => match (s.strip_suffix('%'), s.strip_suffix("fr")) {
+                (Some(percent), _) => percent.trim().parse().ok().filter(|p| *p <= 100).map(Extent::Percent),
+                (_, Some(share)) => share.trim().parse().ok().filter(|s| *s > 0).map(Extent::Share),
+                _ => None
+            }
+        }
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Size::Chars(n) => write!(f, "{}", n),
+            Size::Relative(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
 pub struct Layout {
     pub kind: String,
+    #[allow(dead_code)]
     pub layout_id: Option<String>,
     pub main: Option<bool>,
     pub children: Option<Vec<Layout>>,
     pub orientation: Option<String>,
-    pub width: Option<usize>,
-    pub height: Option<usize>,
+    pub width: Option<Size>,
+    pub height: Option<Size>,
+    pub min_width: Option<usize>,
+    pub max_width: Option<usize>,
+    pub min_height: Option<usize>,
+    pub max_height: Option<usize>,
     pub task_id: Option<String>,
+    pub overflow: Option<String>,
+    pub follow: Option<bool>,
+    pub border: Option<String>,
+    pub margin: Option<usize>,
+    pub padding: Option<usize>,
+    pub align: Option<String>,
+    pub valign: Option<String>,
+    pub columns: Option<Vec<Size>>,
+    pub rows: Option<Vec<Size>>,
+    pub gap: Option<usize>,
+    pub row: Option<usize>,
+    pub column: Option<usize>,
+    pub row_span: Option<usize>,
+    pub column_span: Option<usize>,
+    pub title: Option<String>,
+    pub rotate: Option<String>,
 }
 
 impl Layout {
+    /// The textviews in the layout, in the order they're laid out.
+    pub fn text_views(&self) -> Vec<&Layout> {
+        match self.kind.deref() {
+            "textview" => vec![self],
+            _ => self.children.iter().flatten().flat_map(Layout::text_views).collect()
+        }
+    }
+
+    /// The task shown in the 'main' view.
+    pub fn main_task_id(&self) -> Option<String> {
+        self.text_views().into_iter().find(|l| l.main.unwrap_or(false)).and_then(|l| l.task_id.clone())
+    }
 
     pub fn to_str(&self, depth: usize) -> Option<String> {
-        let mut out = String::from(format!("{:indent$}{}", "", self.kind.clone(), indent=depth*2));
+        let mut out = format!("{:indent$}{}", "", self.kind.clone(), indent=depth*2);
         match self.kind.deref() {
             "linearlayout" => { out += format!(" ({})\n", self.orientation.as_ref().unwrap_or(&String::from("unknown"))).as_ref() },
             "textview" => { out += format!(" ({})\n", self.task_id.as_ref().unwrap_or(&String::from(""))).as_ref() }
+            "tabs" => { out += format!(" ({} pages)\n", self.children.as_ref().map_or(0, Vec::len)).as_ref() },
+            "grid" => { out += format!(" ({} columns)\n", self.columns.as_ref().map_or(0, Vec::len)).as_ref() },
             "panel" => { out+= format!(" ({} children)\n", self.children.as_ref().unwrap_or(Vec::new().as_ref()).len()).as_ref() },
             _ => { out += "Unknown" }
         }
 
         for child in self.children.as_ref().unwrap_or(&Vec::new()) {
-            out += child.to_str(depth + 1).unwrap_or(String::from("")).as_ref();
+            out += child.to_str(depth + 1).unwrap_or_default().as_ref();
         }
 
         Some(out)
@@ -62,41 +271,46 @@ impl fmt::Display for Layout {
     }
 }
 
-pub fn load_task_config() -> Option<Config> {
-    let mut tasks_file = File::open("config/tasks.toml").unwrap();
-    let mut toml_tasks = String::new();
-    tasks_file.read_to_string(&mut toml_tasks).unwrap();
-    let config = toml::from_str(&toml_tasks);
+pub fn load_task_config(path: &Path) -> Result<Config, Vec<ConfigError>> {
+    let toml_tasks = fs::read_to_string(path).map_err(|err|
+        vec![ConfigError::new(ConfigErrorKind::Unreadable(err.to_string()), None)]
+    )?;
 
-    match config {
-        Ok(conf) => {
-            let conf = populate_layout_ids(conf)?;
-            match how_many_mains(&conf.layout) {
-                0 => { panic!("No 'main' layout! Mark one of your textviews as being 'main'"); },
-                1 => { Some(conf) }, // perfect!
-                _ => { panic!("More than one 'main' textview in tasks.toml!"); }
-            }
-        },
-        Err(err) => {
-            println!("conf err: {}", err);
-            None
+    parse_task_config(&toml_tasks)
+}
+
+pub fn parse_task_config(toml_tasks: &str) -> Result<Config, Vec<ConfigError>> {
+    let conf = toml::from_str(toml_tasks).map_err(|err| vec![ConfigError::from_toml(toml_tasks, err)])?;
+
+    let errors = validation::validate(toml_tasks, &conf);
+    if !errors.is_empty() { return Err(errors); }
+
+    Ok(link_dependencies(apply_defaults(populate_layout_ids(conf))))
+}
+
+pub fn apply_defaults(mut conf: Config) -> Config {
+    for task in conf.tasks.iter_mut() {
+        for (name, value) in &conf.defaults.env {
+            task.env.entry(name.clone()).or_insert_with(|| value.clone());
         }
     }
+    conf
+}
+
+pub fn link_dependencies(mut conf: Config) -> Config {
+    dependencies::link(&mut conf.tasks);
+    conf
 }
 
-pub fn populate_layout_ids(conf: Config) -> Option<Config> {
-    Some(conf)
+pub fn populate_layout_ids(conf: Config) -> Config {
+    conf
 }
 
 pub fn how_many_mains(l: &Layout) -> usize {
     let main_children = match &l.children {
-        Some(children) => { children.iter().map(|c| how_many_mains(c)).sum() },
+        Some(children) => { children.iter().map(how_many_mains).sum() },
         None => 0
     };
 
-    let total_mains = if l.main.unwrap_or(false) { 1 } else { 0 } + main_children;
-
-    if l.main.unwrap_or(false) && l.kind != "textview".to_string() { panic!("only textview's can be 'main'"); }
-
-    total_mains
+    (if l.main.unwrap_or(false) { 1 } else { 0 }) + main_children
 }
\ No newline at end of file
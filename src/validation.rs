@@ -0,0 +1,613 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::dependencies;
+use crate::env_file::load_env_file;
+use crate::file_watcher::check_pattern;
+use crate::process::command_line;
+use crate::schedule::{parse_duration, Schedule};
+use crate::tasks::{Config, Extent, Layout, Shell, TaskMode, how_many_mains};
+use crate::TaskId;
+
+const LAYOUT_KINDS: [&str; 4] = ["linearlayout", "textview", "grid", "tabs"];
+const ORIENTATIONS: [&str; 2] = ["horizontal", "vertical"];
+const OVERFLOWS: [&str; 4] = ["truncate", "wrap", "wrap-words", "ellipsis"];
+const BORDERS: [&str; 4] = ["none", "single", "double", "rounded"];
+const ALIGNS: [&str; 3] = ["left", "center", "right"];
+const VALIGNS: [&str; 3] = ["top", "middle", "bottom"];
+
+/***
+Location: A 1-based line and column in the config file.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn from_offset(source: &str, offset: usize) -> Location {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+        Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/***
+ConfigError: Something wrong with a config file, and where we found it.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigError {
+    pub location: Option<Location>,
+    pub kind: ConfigErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigErrorKind {
+    Unreadable(String),
+    Syntax(String),
+    DuplicateTaskId(TaskId),
+    BadPeriod { task_id: TaskId, period: String },
+    BadSchedule { task_id: TaskId, schedule: String, reason: String },
+    PeriodAndSchedule(TaskId),
+    BadTimeout { task_id: TaskId, timeout: String },
+    BadCommand { task_id: TaskId, reason: String },
+    BadEnvFile { task_id: TaskId, reason: String },
+    BadWatch { task_id: TaskId, pattern: String, reason: String },
+    ScheduledStream(TaskId),
+    PtyWithStdin(TaskId),
+    UnknownDependency { task_id: TaskId, dependency: TaskId },
+    DependencyCycle(Vec<TaskId>),
+    UnknownLayoutKind(String),
+    MissingOrientation,
+    UnknownOrientation(String),
+    UnknownOverflow(String),
+    UnknownBorder(String),
+    UnknownAlign(String),
+    UnknownVAlign(String),
+    InvalidSize(String),
+    SizeWithBounds(&'static str),
+    MinAboveMax(&'static str),
+    MissingColumns,
+    OutsideGrid(usize),
+    BadRotate(String),
+    MissingTaskId,
+    UnknownTaskId(TaskId),
+    MainNotTextView(String),
+    NoMainView,
+    MultipleMainViews,
+}
+
+impl ConfigError {
+    pub fn new(kind: ConfigErrorKind, location: Option<Location>) -> ConfigError {
+        ConfigError { location, kind }
+    }
+
+    pub fn from_toml(source: &str, err: toml::de::Error) -> ConfigError {
+        let mut message = err.to_string();
+        let location = err.line_col().map(|(line, col)| {
+            // toml tacks the position onto the end of its message - we report it separately.
+            let suffix = format!(" at line {} column {}", line + 1, col + 1);
+            if message.ends_with(&suffix) { message.truncate(message.len() - suffix.len()); }
+
+            let offset: usize = source.split_inclusive('\n').take(line).map(|l| l.len()).sum();
+            Location::from_offset(source, offset + col)
+        });
+
+        ConfigError::new(ConfigErrorKind::Syntax(message), location)
+    }
+}
+
+impl fmt::Display for ConfigErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigErrorKind::Unreadable(err) => write!(f, "couldn't read config: {}", err),
+            ConfigErrorKind::Syntax(err) => write!(f, "{}", err),
+            ConfigErrorKind::DuplicateTaskId(id) => write!(f, "task id '{}' is used more than once", id),
+            ConfigErrorKind::BadPeriod { task_id, period } => write!(f, "task '{}' has a bad period '{}' - expected a duration like 30s, 500ms or 1h30m", task_id, period),
+            ConfigErrorKind::BadSchedule { task_id, schedule, reason } => write!(f, "task '{}' has a bad schedule '{}': {}", task_id, schedule, reason),
+            ConfigErrorKind::PeriodAndSchedule(task_id) => write!(f, "task '{}' has both a period and a schedule - pick one", task_id),
+            ConfigErrorKind::BadTimeout { task_id, timeout } => write!(f, "task '{}' has a bad timeout '{}' - expected a duration like 30s, 500ms or 1h30m", task_id, timeout),
+            ConfigErrorKind::BadCommand { task_id, reason } => write!(f, "task '{}' has a bad command: {}", task_id, reason),
+            ConfigErrorKind::BadEnvFile { task_id, reason } => write!(f, "task '{}' has a bad env_file: {}", task_id, reason),
+            ConfigErrorKind::BadWatch { task_id, pattern, reason } => write!(f, "task '{}' has a bad watch pattern '{}': {}", task_id, pattern, reason),
+            ConfigErrorKind::ScheduledStream(task_id) => write!(f, "task '{}' streams continuously, so it can't have a period, schedule or watch", task_id),
+            ConfigErrorKind::PtyWithStdin(task_id) => write!(f, "task '{}' runs in a pty, so it can't read another task's output with stdin_from", task_id),
+            ConfigErrorKind::UnknownDependency { task_id, dependency } => write!(f, "task '{}' depends on '{}', which isn't a task", task_id, dependency),
+            ConfigErrorKind::DependencyCycle(cycle) => write!(f, "tasks can't run after themselves: {}", cycle.join(" runs after ")),
+            ConfigErrorKind::UnknownLayoutKind(kind) => write!(f, "unknown layout kind '{}' - expected one of {}", kind, LAYOUT_KINDS.join(", ")),
+            ConfigErrorKind::MissingOrientation => write!(f, "linearlayout is missing an 'orientation'"),
+            ConfigErrorKind::UnknownOrientation(o) => write!(f, "unknown orientation '{}' - expected one of {}", o, ORIENTATIONS.join(", ")),
+            ConfigErrorKind::UnknownOverflow(o) => write!(f, "unknown overflow '{}' - expected one of {}", o, OVERFLOWS.join(", ")),
+            ConfigErrorKind::UnknownBorder(b) => write!(f, "unknown border '{}' - expected one of {}", b, BORDERS.join(", ")),
+            ConfigErrorKind::UnknownAlign(a) => write!(f, "unknown align '{}' - expected one of {}", a, ALIGNS.join(", ")),
+            ConfigErrorKind::UnknownVAlign(a) => write!(f, "unknown valign '{}' - expected one of {}", a, VALIGNS.join(", ")),
+            ConfigErrorKind::InvalidSize(s) => write!(f, "invalid size '{}' - expected a number of characters, a percentage like \"30%\", a share like \"2fr\", \"fill\" or \"auto\"", s),
+            ConfigErrorKind::SizeWithBounds(dim) => write!(f, "{0} can't be given with min_{0} or max_{0} - they're for views sized by their content", dim),
+            ConfigErrorKind::MinAboveMax(dim) => write!(f, "min_{0} is more than max_{0}", dim),
+            ConfigErrorKind::MissingColumns => write!(f, "grid is missing its 'columns'"),
+            ConfigErrorKind::BadRotate(rotate) => write!(f, "tabs have a bad rotate '{}' - expected a duration of at least a second, like 30s or 1m", rotate),
+            ConfigErrorKind::OutsideGrid(columns) => write!(f, "this goes past the last of the grid's {} columns", columns),
+            ConfigErrorKind::MissingTaskId => write!(f, "textview is missing a 'task_id'"),
+            ConfigErrorKind::UnknownTaskId(id) => write!(f, "textview refers to unknown task '{}'", id),
+            ConfigErrorKind::MainNotTextView(kind) => write!(f, "only textviews can be 'main', but this is a {}", kind),
+            ConfigErrorKind::NoMainView => write!(f, "no 'main' view - mark one of your textviews as being 'main'"),
+            ConfigErrorKind::MultipleMainViews => write!(f, "more than one textview is marked 'main'"),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some(loc) => write!(f, "{}:{}: {}", loc.line, loc.column, self.kind),
+            None => write!(f, "{}", self.kind)
+        }
+    }
+}
+
+/***
+Anchors: A shadow of the Config that only records where each task and layout starts,
+    so errors found in the real Config can point back into the file.
+ */
+#[derive(Deserialize)]
+struct ConfigAnchors {
+    tasks: Vec<TaskAnchor>,
+    layout: LayoutAnchor,
+}
+
+#[derive(Deserialize)]
+struct TaskAnchor {
+    id: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+struct LayoutAnchor {
+    kind: Spanned<String>,
+    children: Option<Vec<LayoutAnchor>>,
+}
+
+struct Validator<'a> {
+    source: &'a str,
+    task_ids: HashSet<&'a str>,
+    errors: Vec<ConfigError>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, kind: ConfigErrorKind, offset: Option<usize>) {
+        let location = offset.map(|o| Location::from_offset(self.source, o));
+        self.errors.push(ConfigError::new(kind, location));
+    }
+
+    fn check_tasks(&mut self, config: &'a Config, anchors: &[TaskAnchor]) {
+        for (i, task) in config.tasks.iter().enumerate() {
+            let offset = anchors.get(i).map(|a| a.id.start());
+
+            if !self.task_ids.insert(task.id.as_str()) {
+                self.error(ConfigErrorKind::DuplicateTaskId(task.id.clone()), offset);
+            }
+
+            match (&task.period, &task.schedule) {
+                (Some(_), Some(_)) => self.error(ConfigErrorKind::PeriodAndSchedule(task.id.clone()), offset),
+                (None, None) => {}, // Manual, or only run when watched files change
+                (Some(period), None) => if parse_duration(period).is_none_or(|p| p.is_zero()) {
+                    self.error(ConfigErrorKind::BadPeriod { task_id: task.id.clone(), period: period.clone() }, offset);
+                },
+                (None, Some(schedule)) => if let Err(reason) = Schedule::parse(schedule) {
+                    self.error(ConfigErrorKind::BadSchedule { task_id: task.id.clone(), schedule: schedule.clone(), reason }, offset);
+                }
+            }
+
+            if let Err(err) = command_line(&task.command, task.shell.as_ref().and_then(Shell::program).as_deref()) {
+                self.error(ConfigErrorKind::BadCommand { task_id: task.id.clone(), reason: err.to_string() }, offset);
+            }
+
+            if let Some(Err(reason)) = task.env_file.as_ref().map(|f| load_env_file(&Path::new(&task.path).join(f))) {
+                self.error(ConfigErrorKind::BadEnvFile { task_id: task.id.clone(), reason }, offset);
+            }
+
+            if let Some(timeout) = task.timeout.as_ref().filter(|t| parse_duration(t).is_none_or(|d| d.is_zero())) {
+                self.error(ConfigErrorKind::BadTimeout { task_id: task.id.clone(), timeout: timeout.clone() }, offset);
+            }
+
+            if task.mode == TaskMode::Stream && (task.period.is_some() || task.schedule.is_some() || !task.watch.is_empty()) {
+                self.error(ConfigErrorKind::ScheduledStream(task.id.clone()), offset);
+            }
+
+            if task.pty && task.stdin_from.is_some() {
+                self.error(ConfigErrorKind::PtyWithStdin(task.id.clone()), offset);
+            }
+
+            for pattern in &task.watch {
+                if let Err(reason) = check_pattern(pattern) {
+                    self.error(ConfigErrorKind::BadWatch { task_id: task.id.clone(), pattern: pattern.clone(), reason }, offset);
+                }
+            }
+        }
+    }
+
+    /// Must run after check_tasks, so every task id is known.
+    fn check_dependencies(&mut self, config: &Config, anchors: &[TaskAnchor]) {
+        let offset_of = |id: &str| config.tasks.iter().position(|t| t.id == id).and_then(|i| anchors.get(i)).map(|a| a.id.start());
+
+        for task in &config.tasks {
+            let unknown: Vec<&String> = task.after.iter().chain(&task.on_success).chain(&task.stdin_from).
+                filter(|d| !self.task_ids.contains(d.as_str())).
+                collect();
+            for dependency in unknown {
+                self.error(ConfigErrorKind::UnknownDependency { task_id: task.id.clone(), dependency: dependency.clone() }, offset_of(&task.id));
+            }
+        }
+
+        let mut linked = config.tasks.clone();
+        dependencies::link(&mut linked);
+        if let Some(cycle) = dependencies::find_cycle(&linked) {
+            let offset = offset_of(&cycle[0]);
+            self.error(ConfigErrorKind::DependencyCycle(cycle), offset);
+        }
+    }
+
+    fn check_layout(&mut self, layout: &Layout, anchor: Option<&LayoutAnchor>) {
+        let offset = anchor.map(|a| a.kind.start());
+
+        match layout.kind.as_str() {
+            "linearlayout" => match layout.orientation.as_deref() {
+                None => self.error(ConfigErrorKind::MissingOrientation, offset),
+                Some(o) if !ORIENTATIONS.contains(&o) => self.error(ConfigErrorKind::UnknownOrientation(o.to_string()), offset),
+                Some(_) => {}
+            },
+            "textview" => match layout.task_id.as_deref() {
+                None => self.error(ConfigErrorKind::MissingTaskId, offset),
+                Some(id) if !self.task_ids.contains(id) => self.error(ConfigErrorKind::UnknownTaskId(id.to_string()), offset),
+                Some(_) => {}
+            },
+            "grid" => match &layout.columns {
+                None => self.error(ConfigErrorKind::MissingColumns, offset),
+                Some(columns) => {
+                    for size in columns.iter().chain(layout.rows.iter().flatten()).filter(|s| s.extent().is_none()) {
+                        self.error(ConfigErrorKind::InvalidSize(size.to_string()), offset);
+                    }
+
+                    let child_anchors = anchor.and_then(|a| a.children.as_ref());
+                    for (i, child) in layout.children.iter().flatten().enumerate() {
+                        if child.column.unwrap_or(0) + child.column_span.unwrap_or(1).max(1) > columns.len() {
+                            let child_offset = child_anchors.and_then(|a| a.get(i)).map(|a| a.kind.start());
+                            self.error(ConfigErrorKind::OutsideGrid(columns.len()), child_offset);
+                        }
+                    }
+                }
+            },
+            // Any quicker, and nobody could read a page before it turned
+            "tabs" => if let Some(rotate) = layout.rotate.as_ref().filter(|r| parse_duration(r).is_none_or(|d| d < Duration::from_secs(1))) {
+                self.error(ConfigErrorKind::BadRotate(rotate.clone()), offset);
+            },
+            kind => self.error(ConfigErrorKind::UnknownLayoutKind(kind.to_string()), offset),
+        }
+
+        if let Some(overflow) = layout.overflow.as_deref().filter(|o| !OVERFLOWS.contains(o)) {
+            self.error(ConfigErrorKind::UnknownOverflow(overflow.to_string()), offset);
+        }
+
+        if let Some(border) = layout.border.as_deref().filter(|b| !BORDERS.contains(b)) {
+            self.error(ConfigErrorKind::UnknownBorder(border.to_string()), offset);
+        }
+
+        if let Some(align) = layout.align.as_deref().filter(|a| !ALIGNS.contains(a)) {
+            self.error(ConfigErrorKind::UnknownAlign(align.to_string()), offset);
+        }
+
+        if let Some(valign) = layout.valign.as_deref().filter(|a| !VALIGNS.contains(a)) {
+            self.error(ConfigErrorKind::UnknownVAlign(valign.to_string()), offset);
+        }
+
+        for (dim, size, least, most) in [("width", &layout.width, layout.min_width, layout.max_width),
+                                         ("height", &layout.height, layout.min_height, layout.max_height)] {
+            match (size, least, most) {
+                (Some(size), _, _) if size.extent().is_none() => self.error(ConfigErrorKind::InvalidSize(size.to_string()), offset),
+                (Some(size), Some(_), _) | (Some(size), _, Some(_)) if size.extent() != Some(Extent::Auto) => self.error(ConfigErrorKind::SizeWithBounds(dim), offset),
+                (_, Some(least), Some(most)) if least > most => self.error(ConfigErrorKind::MinAboveMax(dim), offset),
+                _ => {}
+            }
+        }
+
+        if layout.main.unwrap_or(false) && layout.kind != "textview" {
+            self.error(ConfigErrorKind::MainNotTextView(layout.kind.clone()), offset);
+        }
+
+        let child_anchors = anchor.and_then(|a| a.children.as_ref());
+        for (i, child) in layout.children.iter().flatten().enumerate() {
+            self.check_layout(child, child_anchors.and_then(|a| a.get(i)));
+        }
+    }
+}
+
+/***
+validate: Check a parsed Config for problems, returning all of them.
+    `source` is the text the Config was parsed from, used to locate each problem.
+ */
+pub fn validate(source: &str, config: &Config) -> Vec<ConfigError> {
+    // If the real Config parsed, so will its anchors. If not, we just can't say where errors are.
+    let anchors: Option<ConfigAnchors> = toml::from_str(source).ok();
+    let mut validator = Validator { source, task_ids: HashSet::new(), errors: vec![] };
+
+    validator.check_tasks(config, anchors.as_ref().map(|a| a.tasks.as_slice()).unwrap_or(&[]));
+    validator.check_dependencies(config, anchors.as_ref().map(|a| a.tasks.as_slice()).unwrap_or(&[]));
+    validator.check_layout(&config.layout, anchors.as_ref().map(|a| &a.layout));
+
+    match how_many_mains(&config.layout) {
+        0 => validator.error(ConfigErrorKind::NoMainView, None),
+        1 => {}, // perfect!
+        _ => validator.error(ConfigErrorKind::MultipleMainViews, None),
+    }
+
+    validator.errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::parse_task_config;
+
+    const TASKS: &str = r#"
+[[tasks]]
+    id = "time"
+    name = "Date and Time"
+    description = "See the current date and time"
+    path = "."
+    command = "date"
+    period = "1s"
+"#;
+
+    fn errors_for(layout: &str) -> Vec<ConfigError> {
+        parse_task_config(&format!("{}{}", TASKS, layout)).err().unwrap_or_default()
+    }
+
+    fn with_layout(tasks: &str) -> String {
+        format!("{}[layout]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\n", tasks)
+    }
+
+    #[test]
+    fn valid_config_has_no_errors() {
+        assert!(parse_task_config(&with_layout(TASKS)).is_ok());
+    }
+
+    #[test]
+    fn location_from_offset_is_one_based() {
+        assert_eq!(Location::from_offset("ab\ncd", 0), Location { line: 1, column: 1 });
+        assert_eq!(Location::from_offset("ab\ncd", 4), Location { line: 2, column: 2 });
+    }
+
+    #[test]
+    fn syntax_errors_are_located() {
+        let errors = parse_task_config("[[tasks]]\nid = \n").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location.map(|l| l.line), Some(2));
+        assert!(!errors[0].to_string().contains("at line"));
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let errors = errors_for(r#"
+[layout]
+kind = "linearlayout"
+
+    [[layout.children]]
+    kind = "textview"
+    task_id = "nope"
+
+    [[layout.children]]
+    kind = "pane"
+"#);
+        let kinds: Vec<ConfigErrorKind> = errors.iter().map(|e| e.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            ConfigErrorKind::MissingOrientation,
+            ConfigErrorKind::UnknownTaskId("nope".to_string()),
+            ConfigErrorKind::UnknownLayoutKind("pane".to_string()),
+            ConfigErrorKind::NoMainView,
+        ]);
+        assert_eq!(errors[1].location, Some(Location { line: 14, column: 12 }));
+    }
+
+    #[test]
+    fn reports_unknown_overflows() {
+        let errors = errors_for("[layout]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\noverflow = \"scroll\"\n");
+        assert_eq!(errors[0].kind, ConfigErrorKind::UnknownOverflow("scroll".to_string()));
+        assert!(errors_for("[layout]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\noverflow = \"wrap-words\"\n").is_empty());
+    }
+
+    #[test]
+    fn reports_sizes_which_make_no_sense() {
+        let layout = |sizes: &str| errors_for(&format!("[layout]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\n{}\n", sizes));
+        let kinds = |sizes: &str| layout(sizes).into_iter().map(|e| e.kind).collect::<Vec<ConfigErrorKind>>();
+
+        assert_eq!(kinds("width = \"150%\"\nheight = \"big\""), vec![
+            ConfigErrorKind::InvalidSize("150%".to_string()),
+            ConfigErrorKind::InvalidSize("big".to_string()),
+        ]);
+        assert_eq!(kinds("width = 10\nmax_width = 20"), vec![ConfigErrorKind::SizeWithBounds("width")]);
+        assert_eq!(kinds("min_height = 5\nmax_height = 2"), vec![ConfigErrorKind::MinAboveMax("height")]);
+        assert!(kinds("width = \"30%\"\nheight = \"fill\"").is_empty());
+        assert!(kinds("width = \"2fr\"\nmin_height = 2\nmax_height = 5").is_empty());
+    }
+
+    #[test]
+    fn reports_grids_which_make_no_sense() {
+        let grid = |grid: &str| errors_for(&format!(
+            "[layout]\nkind = \"grid\"\n{}\n[[layout.children]]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\ncolumn = 1\n", grid));
+        let kinds = |g: &str| grid(g).into_iter().map(|e| e.kind).collect::<Vec<ConfigErrorKind>>();
+
+        assert_eq!(kinds("rows = [1]"), vec![ConfigErrorKind::MissingColumns]);
+        assert_eq!(kinds("columns = [\"auto\", \"lots\"]"), vec![ConfigErrorKind::InvalidSize("lots".to_string())]);
+        assert_eq!(kinds("columns = [10]"), vec![ConfigErrorKind::OutsideGrid(1)]);
+        assert!(grid("columns = [10]")[0].location.is_some());
+        assert!(kinds("columns = [\"auto\", \"fill\"]\nrows = [\"30%\"]\ngap = 1").is_empty());
+    }
+
+    #[test]
+    fn reports_unknown_alignments() {
+        let layout = |align: &str| errors_for(&format!("[layout]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\n{}\n", align));
+        let kinds = |align: &str| layout(align).into_iter().map(|e| e.kind).collect::<Vec<ConfigErrorKind>>();
+
+        assert_eq!(kinds("align = \"middle\"\nvalign = \"center\""), vec![
+            ConfigErrorKind::UnknownAlign("middle".to_string()),
+            ConfigErrorKind::UnknownVAlign("center".to_string()),
+        ]);
+        assert!(kinds("align = \"center\"\nvalign = \"middle\"\npadding = 1\nmargin = 2").is_empty());
+    }
+
+    #[test]
+    fn reports_bad_rotations() {
+        let tabs = |rotate: &str| errors_for(&format!(
+            "[layout]\nkind = \"tabs\"\nrotate = \"{}\"\n[[layout.children]]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\n", rotate));
+        assert_eq!(tabs("sometimes")[0].kind, ConfigErrorKind::BadRotate("sometimes".to_string()));
+        assert_eq!(tabs("0s")[0].kind, ConfigErrorKind::BadRotate("0s".to_string()));
+        assert_eq!(tabs("500ms")[0].kind, ConfigErrorKind::BadRotate("500ms".to_string()));
+        assert!(tabs("30s").is_empty());
+    }
+
+    #[test]
+    fn reports_unknown_borders() {
+        let errors = errors_for("[layout]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\nborder = \"dotted\"\n");
+        assert_eq!(errors[0].kind, ConfigErrorKind::UnknownBorder("dotted".to_string()));
+        assert!(errors_for("[layout]\nkind = \"textview\"\ntask_id = \"time\"\nmain = true\nborder = \"single\"\n").is_empty());
+    }
+
+    #[test]
+    fn reports_duplicate_ids_and_bad_periods() {
+        let source = format!("{}{}", TASKS, TASKS.replace("\"1s\"", "\"soon\""));
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::DuplicateTaskId("time".to_string()));
+        assert_eq!(errors[0].location.map(|l| l.line), Some(11));
+        assert_eq!(errors[1].kind, ConfigErrorKind::BadPeriod { task_id: "time".to_string(), period: "soon".to_string() });
+    }
+
+    #[test]
+    fn reports_bad_schedules() {
+        let source = TASKS.replace("period = \"1s\"", "schedule = \"0 25 * * *\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert!(matches!(&errors[0].kind, ConfigErrorKind::BadSchedule { task_id, .. } if task_id == "time"));
+
+        let source = TASKS.replace("period = \"1s\"", "period = \"1s\"\n    schedule = \"@daily\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::PeriodAndSchedule("time".to_string()));
+
+        let source = TASKS.replace("period = \"1s\"", "schedule = \"*/5 9-17 * * mon-fri\"");
+        assert!(parse_task_config(&with_layout(&source)).is_ok());
+    }
+
+    #[test]
+    fn reports_periods_of_nothing() {
+        for period in ["0", "0s", "0ms"] {
+            let source = TASKS.replace("\"1s\"", &format!("\"{}\"", period));
+            let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+            assert_eq!(errors[0].kind, ConfigErrorKind::BadPeriod { task_id: "time".to_string(), period: period.to_string() });
+        }
+    }
+
+    #[test]
+    fn tasks_without_a_period_are_manual() {
+        let config = parse_task_config(&with_layout(&TASKS.replace("period = \"1s\"", ""))).unwrap();
+        assert!(config.tasks[0].is_manual());
+
+        let config = parse_task_config(&with_layout(&TASKS.replace("period = \"1s\"", "period = \"1s\"\n    trigger = \"manual\""))).unwrap();
+        assert!(config.tasks[0].is_manual());
+
+        let config = parse_task_config(&with_layout(TASKS)).unwrap();
+        assert!(!config.tasks[0].is_manual());
+
+        let config = parse_task_config(&with_layout(&TASKS.replace("period = \"1s\"", "watch = [\"src/**/*.rs\"]"))).unwrap();
+        assert!(!config.tasks[0].is_manual());
+    }
+
+    #[test]
+    fn reports_bad_watch_patterns() {
+        let source = TASKS.replace("period = \"1s\"", "watch = [\"src/[\"]");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert!(matches!(&errors[0].kind, ConfigErrorKind::BadWatch { task_id, pattern, .. } if task_id == "time" && pattern == "src/["));
+    }
+
+    #[test]
+    fn reports_unknown_and_circular_dependencies() {
+        let source = TASKS.replace("period = \"1s\"", "period = \"1s\"\n    after = [\"build\"]");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::UnknownDependency { task_id: "time".to_string(), dependency: "build".to_string() });
+
+        let source = TASKS.replace("period = \"1s\"", "period = \"1s\"\n    on_success = \"time\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::DependencyCycle(vec!["time".to_string(), "time".to_string()]));
+        assert_eq!(errors[0].location.map(|l| l.line), Some(3));
+    }
+
+    #[test]
+    fn streams_cannot_be_scheduled() {
+        let source = TASKS.replace("period = \"1s\"", "period = \"1s\"\n    mode = \"stream\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::ScheduledStream("time".to_string()));
+
+        let source = TASKS.replace("period = \"1s\"", "mode = \"stream\"");
+        let config = parse_task_config(&with_layout(&source)).unwrap();
+        assert!(!config.tasks[0].is_manual());
+    }
+
+    #[test]
+    fn ptys_cannot_be_piped_into() {
+        let source = TASKS.replace("period = \"1s\"", "period = \"1s\"\n    pty = true\n    stdin_from = \"time\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::PtyWithStdin("time".to_string()));
+    }
+
+    #[test]
+    fn reports_unparseable_commands() {
+        let source = TASKS.replace("command = \"date\"", "command = \"echo 'oops\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert!(matches!(&errors[0].kind, ConfigErrorKind::BadCommand { task_id, .. } if task_id == "time"));
+    }
+
+    #[test]
+    fn reports_missing_env_files() {
+        let source = TASKS.replace("period = \"1s\"", "period = \"1s\"\n    env_file = \"no-such-file.env\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert!(matches!(&errors[0].kind, ConfigErrorKind::BadEnvFile { task_id, .. } if task_id == "time"));
+    }
+
+    #[test]
+    fn reports_bad_timeouts() {
+        let source = TASKS.replace("period = \"1s\"", "period = \"1s\"\n    timeout = \"a while\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadTimeout { task_id: "time".to_string(), timeout: "a while".to_string() });
+
+        let source = TASKS.replace("period = \"1s\"", "period = \"1s\"\n    timeout = \"0s\"");
+        let errors = parse_task_config(&with_layout(&source)).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadTimeout { task_id: "time".to_string(), timeout: "0s".to_string() });
+    }
+
+    #[test]
+    fn only_one_textview_can_be_main() {
+        let errors = errors_for(r#"
+[layout]
+kind = "linearlayout"
+orientation = "vertical"
+main = true
+
+    [[layout.children]]
+    kind = "textview"
+    task_id = "time"
+    main = true
+"#);
+        let kinds: Vec<ConfigErrorKind> = errors.iter().map(|e| e.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            ConfigErrorKind::MainNotTextView("linearlayout".to_string()),
+            ConfigErrorKind::MultipleMainViews,
+        ]);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::widgets::{Border, BorderStyle, View, Dim, CharDims, Region, Style, Color, StyledLine, display_width};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+// The box drawing characters for each corner and side: top left, top right, bottom left, bottom right, across, down
+const SINGLE: [char; 6] = ['┌', '┐', '└', '┘', '─', '│'];
+const DOUBLE: [char; 6] = ['╔', '╗', '╚', '╝', '═', '║'];
+const ROUNDED: [char; 6] = ['╭', '╮', '╰', '╯', '─', '│'];
+
+impl Border {
+    pub fn new(child: Rc<RefCell<dyn View>>, style: BorderStyle) -> Border {
+        Border { child, style, size: (0, 0) }
+    }
+
+    fn lines(&self) -> [char; 6] {
+        match self.style {
+            BorderStyle::Single => SINGLE,
+            BorderStyle::Double => DOUBLE,
+            BorderStyle::Rounded => ROUNDED
+        }
+    }
+
+    /// The top or bottom of the border - `across` between two corners, with `label` set into it if there's room.
+    /// A label too long to fit is cut short with an ellipsis.
+    fn edge(&self, left: char, right: char, across: char, label: Option<String>) -> String {
+        let inner = self.child.borrow().width();
+        // Leave a line either side, and a space between the label and each of them - "─ label ─".
+        // Tabs and other control characters would throw the edge out, so they show as spaces.
+        let label = label.filter(|_| inner >= 5).map(|l| {
+            let line: String = l.lines().next().unwrap_or_default().chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+            StyledLine::plain(&line).ellipsize(inner - 4)
+        });
+
+        let middle = match label {
+            Some(label) => format!("{} {} {}", across, label.text(), across.to_string().repeat(inner.saturating_sub(3 + label.width()))),
+            None => across.to_string().repeat(inner)
+        };
+        self.paint(&format!("{}{}{}", left, middle, right))
+    }
+
+    /// A piece of the border, lit up if what's inside has the focus.
+    fn paint(&self, text: &str) -> String {
+        match self.child.borrow().focused() {
+            true => format!("{}{}\x1b[0m", Style { fg: Some(Color::Indexed(6)), bold: true, ..Style::default() }.sgr(), text),
+            false => text.to_string()
+        }
+    }
+}
+
+/// A constraint on the view inside, given as one on the view and its border.
+fn grow(dim: Dim, by: usize) -> Dim {
+    match dim {
+        Dim::Fixed(n) => Dim::Fixed(n + by),
+        Dim::UpTo(n) => Dim::UpTo(n + by),
+        Dim::Between(least, most) => Dim::Between(least.saturating_add(by), most.saturating_add(by)),
+        Dim::WrapContent | Dim::Percent(_) | Dim::Weight(_) => dim
+    }
+}
+
+impl View for Border {
+    fn inflate(&mut self, parent_dimensions: &CharDims) -> CharDims {
+        // Without room for both sides of the border, there's no room for anything inside it
+        if parent_dimensions.0 < 2 || parent_dimensions.1 < 2 {
+            self.child.borrow_mut().inflate(&(0, 0));
+            self.size = (0, 0);
+            return self.size;
+        }
+
+        let (width, height) = self.child.borrow_mut().inflate(&(parent_dimensions.0 - 2, parent_dimensions.1 - 2));
+        self.size = (width + 2, height + 2);
+        self.size
+    }
+
+    fn constraints(&self) -> (Dim, Dim) {
+        let (width, height) = self.child.borrow().constraints();
+        (grow(width, 2), grow(height, 2))
+    }
+
+    fn width(&self) -> usize { self.size.0 }
+
+    fn height(&self) -> usize { self.size.1 }
+
+    fn render(&self) -> String {
+        self.render_lines().join("\n")
+    }
+
+    fn render_lines(&self) -> Vec<String> {
+        if self.size == (0, 0) { return vec![String::new()]; }
+
+        let [top_left, top_right, bottom_left, bottom_right, across, down] = self.lines();
+        let child = self.child.borrow();
+        let inner = child.width();
+        let side = self.paint(&down.to_string());
+
+        let mut lines = vec![self.edge(top_left, top_right, across, child.title())];
+        let rows = child.render_lines().into_iter().chain(std::iter::repeat(String::new())).take(child.height());
+        for row in rows {
+            // Pad by what shows on screen, so the right-hand side lines up
+            let pad = inner.saturating_sub(display_width(&row));
+            lines.push(format!("{}{}{:pad$}{}", side, row, "", side, pad = pad));
+        }
+        lines.push(self.edge(bottom_left, bottom_right, across, child.footer()));
+        lines
+    }
+
+    fn focused(&self) -> bool { self.child.borrow().focused() }
+
+    fn regions(&self) -> Vec<Region> {
+        self.child.borrow().regions().into_iter().map(|r| r.offset((1, 1))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::TextView;
+
+    fn bordered(text: &str, width: Dim, height: Dim) -> (Border, Rc<RefCell<TextView>>) {
+        let tv = Rc::new(RefCell::new(TextView::new(width, height)));
+        tv.borrow_mut().update_content(text.to_string());
+        tv.borrow_mut().id("inside");
+        (Border::new(tv.clone(), BorderStyle::Single), tv)
+    }
+
+    #[test]
+    fn draws_a_line_around_its_child() {
+        let (mut border, _) = bordered("one\nthree", Dim::WrapContent, Dim::WrapContent);
+        assert_eq!((7, 4), border.inflate(&(100, 100)));
+        assert_eq!(vec!["┌─────┐", "│one  │", "│three│", "└─────┘"], border.render_lines());
+    }
+
+    #[test]
+    fn sets_the_title_and_footer_into_the_border() {
+        let (mut border, tv) = bordered("some output", Dim::WrapContent, Dim::WrapContent);
+        tv.borrow_mut().show_title(Some("Uptime".to_string()));
+        tv.borrow_mut().show_footer(Some("12:00:00 · 0.2s · exited with 0".to_string()));
+        border.style = BorderStyle::Rounded;
+        border.inflate(&(100, 100));
+        assert_eq!(vec!["╭─ Uptime ──╮", "│some output│", "╰─ 12:00:… ─╯"], border.render_lines());
+    }
+
+    #[test]
+    fn tabs_in_labels_show_as_spaces() {
+        let (mut border, tv) = bordered("some output", Dim::WrapContent, Dim::WrapContent);
+        tv.borrow_mut().show_title(Some("Up\ttime".to_string()));
+        tv.borrow_mut().show_footer(Some("\t\t\t".to_string()));
+        border.inflate(&(100, 100));
+        assert_eq!(vec!["┌─ Up time ─┐", "│some output│", "└─     ─────┘"], border.render_lines());
+    }
+
+    #[test]
+    fn leaves_labels_off_when_there_is_no_room() {
+        let (mut border, tv) = bordered("abc", Dim::WrapContent, Dim::WrapContent);
+        tv.borrow_mut().show_title(Some("Title".to_string()));
+        border.style = BorderStyle::Double;
+        border.inflate(&(100, 100));
+        assert_eq!(vec!["╔═══╗", "║abc║", "╚═══╝"], border.render_lines());
+    }
+
+    #[test]
+    fn leaves_room_for_itself_inside_its_parent() {
+        let (mut border, tv) = bordered("a long line\n2\n3\n4", Dim::WrapContent, Dim::WrapContent);
+        border.inflate(&(6, 4));
+        assert_eq!((4, 2), (tv.borrow().width(), tv.borrow().height()));
+        assert_eq!(vec!["┌────┐", "│a lo│", "│2   │", "└────┘"], border.render_lines());
+
+        assert_eq!((0, 0), border.inflate(&(1, 10)));
+    }
+
+    #[test]
+    fn lights_up_when_its_child_has_the_focus() {
+        let (mut border, tv) = bordered("hi", Dim::WrapContent, Dim::WrapContent);
+        tv.borrow_mut().focus(true);
+        border.inflate(&(100, 100));
+        assert!(border.focused());
+        assert_eq!("\x1b[1;36m│\x1b[0mhi\x1b[1;36m│\x1b[0m", border.render_lines()[1]);
+    }
+
+    #[test]
+    fn regions_are_inside_the_border() {
+        let (mut border, _) = bordered("hi", Dim::WrapContent, Dim::WrapContent);
+        border.inflate(&(100, 100));
+        assert_eq!(vec![Region { id: "inside".to_string(), origin: (1, 1), size: (2, 1) }], border.regions());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::widgets::{Frame, Align, View, Dim, Dimensions, desired_size, content_size, display_width, CharDims, Region};
+use std::cmp::min;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+impl Frame {
+    pub fn new(child: Rc<RefCell<dyn View>>, space: usize, width: Dim, height: Dim) -> Frame {
+        Frame {
+            child,
+            space,
+            align: Align::Start,
+            valign: Align::Start,
+            offset: (0, 0),
+            dims: Dimensions::new(width, height),
+        }
+    }
+
+    pub fn align(&mut self, align: Align, valign: Align) -> &mut Self {
+        self.align = align;
+        self.valign = valign;
+        self
+    }
+}
+
+impl Align {
+    /// How far along a view goes, with `spare` room it doesn't need.
+    fn offset(self, spare: usize) -> usize {
+        match self {
+            Align::Start => 0,
+            Align::Center => spare / 2,
+            Align::End => spare
+        }
+    }
+}
+
+/// How big a frame is - a fixed size holds however little is inside it, so what's inside can be moved around.
+fn size(constraint: Dim, content: usize, offered: usize) -> usize {
+    match constraint {
+        Dim::Fixed(n) => min(n, offered),
+        constraint => content_size(constraint, content, offered)
+    }
+}
+
+impl View for Frame {
+    fn inflate(&mut self, parent_dimensions: &CharDims) -> CharDims {
+        let room = (desired_size(&min(self.dims.width_constraint, Dim::Fixed(parent_dimensions.0))),
+                    desired_size(&min(self.dims.height_constraint, Dim::Fixed(parent_dimensions.1))));
+        let around = self.space * 2;
+
+        let inside = self.child.borrow_mut().inflate(&(room.0.saturating_sub(around), room.1.saturating_sub(around)));
+        self.dims.size = (size(self.dims.width_constraint, inside.0 + around, parent_dimensions.0),
+                          size(self.dims.height_constraint, inside.1 + around, parent_dimensions.1));
+
+        self.offset = (self.space + self.align.offset(self.dims.size.0.saturating_sub(inside.0 + around)),
+                       self.space + self.valign.offset(self.dims.size.1.saturating_sub(inside.1 + around)));
+        self.dims.size
+    }
+
+    fn constraints(&self) -> (Dim, Dim) { (self.dims.width_constraint, self.dims.height_constraint) }
+
+    fn width(&self) -> usize { self.dims.size.0 }
+
+    fn height(&self) -> usize { self.dims.size.1 }
+
+    fn render(&self) -> String {
+        self.render_lines().join("\n")
+    }
+
+    fn render_lines(&self) -> Vec<String> {
+        let child = self.child.borrow();
+        let rendered = child.render_lines();
+        let (left, top) = self.offset;
+
+        (0..self.height()).map(|row| {
+            let line = match row.checked_sub(top).filter(|r| *r < child.height()) {
+                Some(r) => rendered.get(r).map_or("", |l| l.as_str()),
+                None => ""
+            };
+            // Pad by what shows on screen - lines may be full of escape sequences
+            let line = if line.is_empty() { String::new() } else { format!("{:left$}{}", "", line, left = left) };
+            format!("{}{:pad$}", line, "", pad = self.width().saturating_sub(display_width(&line)))
+        }).collect()
+    }
+
+    fn focused(&self) -> bool { self.child.borrow().focused() }
+
+    fn title(&self) -> Option<String> { self.child.borrow().title() }
+
+    fn footer(&self) -> Option<String> { self.child.borrow().footer() }
+
+    fn regions(&self) -> Vec<Region> {
+        self.child.borrow().regions().into_iter().map(|r| r.offset(self.offset)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::TextView;
+
+    fn framed(text: &str, space: usize, width: Dim, height: Dim) -> Frame {
+        let tv = Rc::new(RefCell::new(TextView::new(Dim::WrapContent, Dim::WrapContent)));
+        tv.borrow_mut().update_content(text.to_string());
+        tv.borrow_mut().id("inside");
+        Frame::new(tv, space, width, height)
+    }
+
+    #[test]
+    fn leaves_space_all_the_way_round() {
+        let mut frame = framed("ab\nc", 1, Dim::WrapContent, Dim::WrapContent);
+        assert_eq!((4, 4), frame.inflate(&(80, 24)));
+        assert_eq!(vec!["    ", " ab ", " c  ", "    "], frame.render_lines());
+    }
+
+    #[test]
+    fn puts_what_is_inside_where_it_is_asked() {
+        let mut frame = framed("ab", 0, Dim::Fixed(6), Dim::Fixed(3));
+        frame.align(Align::Center, Align::End).inflate(&(80, 24));
+        assert_eq!(vec!["      ", "      ", "  ab  "], frame.render_lines());
+
+        frame.align(Align::End, Align::Center).inflate(&(80, 24));
+        assert_eq!(vec!["      ", "    ab", "      "], frame.render_lines());
+        assert_eq!(vec![Region { id: "inside".to_string(), origin: (4, 1), size: (2, 1) }], frame.regions());
+    }
+
+    #[test]
+    fn fits_in_what_it_is_offered() {
+        let mut frame = framed("a long line", 2, Dim::Fixed(20), Dim::WrapContent);
+        assert_eq!((10, 5), frame.inflate(&(10, 10)));
+        assert_eq!("  a long  ", frame.render_lines()[2]);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::widgets::{Grid, GridCell, View, Dim, Dimensions, desired_size, content_size, display_width, CharDims, Region};
+use std::cmp::min;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+impl Grid {
+    pub fn new(columns: Vec<Dim>, rows: Vec<Dim>, gap: CharDims, width: Dim, height: Dim) -> Grid {
+        Grid {
+            columns,
+            rows,
+            gap,
+            children: vec![],
+            placed: vec![],
+            dims: Dimensions::new(width, height),
+        }
+    }
+
+    /// Where the next child goes - at `row` and `column`, or in the first free cell in that row or column, or in
+    /// the first free cell of all, going along each row in turn. Rows are added below the ones the grid was given
+    /// as needed - they fit their content.
+    pub fn place(&self, row: Option<usize>, column: Option<usize>, row_span: usize, column_span: usize) -> GridCell {
+        let (row_span, column_span) = (row_span.max(1), column_span.max(1));
+        let fits = |r: usize, c: usize| c + column_span <= self.columns.len().max(column_span) &&
+            !self.children.iter().any(|(cell, _)| overlaps(cell, &GridCell { row: r, column: c, row_span, column_span }));
+
+        let (row, column) = match (row, column) {
+            (Some(r), Some(c)) => (r, c),
+            (Some(r), None) => (r, (0..self.columns.len()).find(|c| fits(r, *c)).unwrap_or(0)),
+            // Nothing is placed below the rows there are, so a cell which won't fit there never fits - it goes there anyway
+            (None, Some(c)) => {
+                let free = self.all_rows().len();
+                ((0..free).find(|r| fits(*r, c)).unwrap_or(free), c)
+            },
+            (None, None) => (0..).flat_map(|r| (0..self.columns.len().max(1)).map(move |c| (r, c))).find(|(r, c)| fits(*r, *c)).unwrap_or((0, 0))
+        };
+
+        GridCell { row, column, row_span, column_span }
+    }
+
+    pub fn add_child(&mut self, child: Rc<RefCell<dyn View>>, cell: GridCell) {
+        self.children.push((cell, child));
+    }
+
+    /// Whether every track the cell spans fits its content - as opposed to being sized some other way.
+    pub fn fits_content(&self, cell: &GridCell) -> (bool, bool) {
+        let rows = self.all_rows();
+        let fits = |tracks: &[Dim], first: usize, span: usize| (first..first + span).all(|t| tracks.get(t).is_none_or(|t| *t == Dim::WrapContent));
+        (fits(&self.columns, cell.column, cell.column_span), fits(&rows, cell.row, cell.row_span))
+    }
+
+    /// The rows the grid was given, and any more its children need - fitting their content.
+    fn all_rows(&self) -> Vec<Dim> {
+        let needed = self.children.iter().map(|(cell, _)| cell.row + cell.row_span).max().unwrap_or(0);
+        let mut rows = self.rows.clone();
+        rows.resize(rows.len().max(needed), Dim::WrapContent);
+        rows
+    }
+}
+
+fn overlaps(a: &GridCell, b: &GridCell) -> bool {
+    a.row < b.row + b.row_span && b.row < a.row + a.row_span &&
+        a.column < b.column + b.column_span && b.column < a.column + a.column_span
+}
+
+/// Where each track starts, given their sizes and the gap between them.
+fn starts(sizes: &[usize], gap: usize) -> Vec<usize> {
+    sizes.iter().scan(0, |start, size| {
+        let this = *start;
+        *start += size + gap;
+        Some(this)
+    }).collect()
+}
+
+/// How much room a child spanning `span` tracks from `first` has - the tracks, and the gaps between them.
+fn span_size(sizes: &[usize], first: usize, span: usize, gap: usize) -> usize {
+    let tracks = &sizes[first.min(sizes.len())..(first + span).min(sizes.len())];
+    tracks.iter().sum::<usize>() + gap * tracks.len().saturating_sub(1)
+}
+
+/***
+size_tracks: How big each track is with `room` to share, and `gap` between each. Fixed tracks get their size,
+    percentages their part of the room, and tracks sized by content what `content` says the biggest child in them
+    needs. Weighted tracks share out what's left. Tracks after the room's run out get nothing.
+ */
+fn size_tracks(tracks: &[Dim], content: &[usize], room: usize, gap: usize) -> Vec<usize> {
+    let mut sizes: Vec<usize> = tracks.iter().zip(content).map(|(track, content)| match track {
+        Dim::Fixed(n) => *n,
+        Dim::Percent(p) => room * p / 100,
+        Dim::Weight(_) => 0,
+        track => content_size(*track, *content, room)
+    }).collect();
+
+    let gaps = gap * tracks.len().saturating_sub(1);
+    let mut space_left = room.saturating_sub(sizes.iter().sum::<usize>() + gaps);
+    let mut weight_left: usize = tracks.iter().map(|t| match t { Dim::Weight(w) => *w, _ => 0 }).sum();
+    for (size, track) in sizes.iter_mut().zip(tracks) {
+        if let Dim::Weight(w) = track {
+            // Out of what's left rather than the whole, so the last one takes up any rounding
+            *size = space_left * w / weight_left;
+            space_left -= *size;
+            weight_left -= w;
+        }
+    }
+
+    let mut room_left = room;
+    for size in sizes.iter_mut() {
+        *size = min(*size, room_left);
+        room_left = room_left.saturating_sub(*size + gap);
+    }
+    sizes
+}
+
+impl View for Grid {
+    fn inflate(&mut self, parent_dimensions: &CharDims) -> CharDims {
+        let available = (desired_size(&min(self.dims.width_constraint, Dim::Fixed(parent_dimensions.0))),
+                         desired_size(&min(self.dims.height_constraint, Dim::Fixed(parent_dimensions.1))));
+        let rows = self.all_rows();
+
+        // Columns which fit their content are as wide as the widest child only in them - and likewise for rows,
+        // once we know how wide each child can be
+        let columns = self.columns.len();
+        let mut widest = vec![0; columns];
+        for (cell, child) in self.children.iter().filter(|(cell, _)| cell.column_span == 1 && cell.column < columns) {
+            widest[cell.column] = widest[cell.column].max(child.borrow_mut().inflate(&available).0);
+        }
+        let column_sizes = size_tracks(&self.columns, &widest, available.0, self.gap.0);
+
+        let mut tallest = vec![0; rows.len()];
+        for (cell, child) in self.children.iter().filter(|(cell, _)| cell.row_span == 1) {
+            let width = span_size(&column_sizes, cell.column, cell.column_span, self.gap.0);
+            tallest[cell.row] = tallest[cell.row].max(child.borrow_mut().inflate(&(width, available.1)).1);
+        }
+        let row_sizes = size_tracks(&rows, &tallest, available.1, self.gap.1);
+
+        let (column_starts, row_starts) = (starts(&column_sizes, self.gap.0), starts(&row_sizes, self.gap.1));
+        self.placed = self.children.iter().map(|(cell, child)| {
+            let space = (span_size(&column_sizes, cell.column, cell.column_span, self.gap.0),
+                         span_size(&row_sizes, cell.row, cell.row_span, self.gap.1));
+            child.borrow_mut().inflate(&space);
+            let origin = (column_starts.get(cell.column).copied().unwrap_or(available.0),
+                          row_starts.get(cell.row).copied().unwrap_or(available.1));
+            (origin, space)
+        }).collect();
+
+        self.dims.size = (content_size(self.dims.width_constraint, span_size(&column_sizes, 0, column_sizes.len(), self.gap.0), parent_dimensions.0),
+                          content_size(self.dims.height_constraint, span_size(&row_sizes, 0, row_sizes.len(), self.gap.1), parent_dimensions.1));
+        self.dims.size
+    }
+
+    fn constraints(&self) -> (Dim, Dim) { (self.dims.width_constraint, self.dims.height_constraint) }
+
+    fn width(&self) -> usize { self.dims.size.0 }
+
+    fn height(&self) -> usize { self.dims.size.1 }
+
+    fn render(&self) -> String {
+        self.render_lines().join("\n")
+    }
+
+    fn render_lines(&self) -> Vec<String> {
+        let rendered: Vec<Vec<String>> = self.children.iter().map(|(_, child)| child.borrow().render_lines()).collect();
+
+        (0..self.height()).map(|y| {
+            // The children across this line, from left to right
+            let mut across: Vec<(usize, &(CharDims, CharDims))> = self.placed.iter().enumerate().
+                filter(|(_, ((_, top), (_, height)))| y >= *top && y < top + height).
+                collect();
+            across.sort_by_key(|(_, ((left, _), _))| *left);
+
+            let mut line = String::new();
+            let mut used = 0;
+            for (i, ((left, top), (width, _))) in across {
+                if *left < used { continue; } // Overlapping another child - it was there first
+                let child = self.children[i].1.borrow();
+                let text = rendered[i].get(y - top).filter(|_| y - top < child.height()).map_or("", |l| l.as_str());
+                let text = if display_width(text) > *width { "" } else { text }; // Shouldn't happen - the child was offered no more
+                line += &format!("{:gap$}{}{:pad$}", "", text, "", gap = left - used, pad = width - display_width(text));
+                used = left + width;
+            }
+
+            // Pad by what shows on screen - lines may be full of escape sequences
+            format!("{}{:pad$}", line, "", pad = self.width().saturating_sub(used))
+        }).collect()
+    }
+
+    fn regions(&self) -> Vec<Region> {
+        self.children.iter().zip(&self.placed).
+            flat_map(|((_, child), (origin, _))| child.borrow().regions().into_iter().map(move |r| r.offset(*origin))).
+            collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::TextView;
+
+    fn text(text: &str) -> Rc<RefCell<TextView>> {
+        let tv = Rc::new(RefCell::new(TextView::new(Dim::WrapContent, Dim::WrapContent)));
+        tv.borrow_mut().update_content(text.to_string());
+        tv
+    }
+
+    #[test]
+    fn sizes_tracks_by_kind() {
+        let tracks = [Dim::Fixed(5), Dim::Percent(10), Dim::WrapContent, Dim::Weight(1), Dim::Weight(3)];
+        assert_eq!(size_tracks(&tracks, &[0, 0, 7, 0, 0], 50, 1), vec![5, 5, 7, 7, 22]);
+        assert_eq!(size_tracks(&[Dim::Fixed(30), Dim::Fixed(30)], &[0, 0], 40, 2), vec![30, 8]);
+    }
+
+    #[test]
+    fn places_children_in_the_next_free_cell() {
+        let mut grid = Grid::new(vec![Dim::Fixed(1); 3], vec![], (0, 0), Dim::WrapContent, Dim::WrapContent);
+        grid.add_child(text("a"), grid.place(None, None, 1, 2));
+        grid.add_child(text("b"), grid.place(None, None, 2, 1));
+        grid.add_child(text("c"), grid.place(None, None, 1, 1));
+        grid.add_child(text("d"), grid.place(Some(3), None, 1, 1));
+        grid.add_child(text("e"), grid.place(None, Some(2), 1, 1));
+
+        let cells: Vec<(usize, usize)> = grid.children.iter().map(|(cell, _)| (cell.row, cell.column)).collect();
+        assert_eq!(cells, vec![(0, 0), (0, 2), (1, 0), (3, 0), (2, 2)]);
+    }
+
+    #[test]
+    fn cells_hanging_off_the_side_go_below_everything() {
+        let mut grid = Grid::new(vec![Dim::Fixed(1); 3], vec![], (0, 0), Dim::WrapContent, Dim::WrapContent);
+        grid.add_child(text("a"), grid.place(None, None, 1, 1));
+        assert_eq!(GridCell { row: 1, column: 2, row_span: 1, column_span: 2 }, grid.place(None, Some(2), 1, 2));
+    }
+
+    #[test]
+    fn columns_line_up_across_rows() {
+        let mut grid = Grid::new(vec![Dim::WrapContent, Dim::Weight(1)], vec![], (1, 0), Dim::Fixed(12), Dim::WrapContent);
+        grid.add_child(text("cpu"), grid.place(None, None, 1, 1));
+        grid.add_child(text("12%"), grid.place(None, None, 1, 1));
+        grid.add_child(text("memory"), grid.place(None, None, 1, 1));
+        grid.add_child(text("1.2G\nfree"), grid.place(None, None, 1, 1));
+        grid.add_child(text("across both"), grid.place(None, None, 1, 2));
+        grid.inflate(&(100, 100));
+
+        assert_eq!((12, 4), (grid.width(), grid.height()));
+        assert_eq!(vec!["cpu    12%  ", "memory 1.2G ", "       free ", "across both "], grid.render_lines());
+    }
+
+    #[test]
+    fn gaps_go_between_rows_too() {
+        let mut grid = Grid::new(vec![Dim::Fixed(2), Dim::Fixed(2)], vec![Dim::Fixed(1), Dim::Fixed(1)], (1, 1), Dim::WrapContent, Dim::WrapContent);
+        grid.add_child(text("a"), grid.place(None, None, 2, 1));
+        grid.add_child(text("b"), grid.place(None, None, 1, 1));
+        grid.add_child(text("c"), grid.place(Some(1), Some(1), 1, 1));
+        grid.inflate(&(100, 100));
+
+        assert_eq!(vec!["a  b ", "     ", "   c "], grid.render_lines());
+    }
+
+    #[test]
+    fn regions_are_where_each_child_was_placed() {
+        let mut grid = Grid::new(vec![Dim::Fixed(4), Dim::Fixed(4)], vec![], (1, 1), Dim::WrapContent, Dim::WrapContent);
+        let (a, b) = (text("a"), text("b"));
+        a.borrow_mut().id("a");
+        b.borrow_mut().id("b");
+        grid.add_child(a, grid.place(None, None, 1, 1));
+        grid.add_child(b, grid.place(Some(1), Some(1), 1, 1));
+        grid.inflate(&(100, 100));
+
+        assert_eq!(grid.regions(), vec![
+            Region { id: "a".to_string(), origin: (0, 0), size: (1, 1) },
+            Region { id: "b".to_string(), origin: (5, 2), size: (1, 1) },
+        ]);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::widgets::{LinearLayout, Orientation, View, Dim, Dimensions, desired_size, CharDims};
+use crate::widgets::{LinearLayout, Orientation, View, Dim, Dimensions, desired_size, content_size, display_width, CharDims, Region};
 use std::cmp::{min, max};
 use std::rc::Rc;
 use std::cell::RefCell;
@@ -8,7 +8,7 @@ use log::info;
 impl LinearLayout {
     pub fn new(orientation: Orientation, width: Dim, height: Dim) -> LinearLayout {
         LinearLayout {
-            orientation: orientation,
+            orientation,
             dims: Dimensions::new(width, height),
             children: vec![],
             visible: true,
@@ -35,22 +35,40 @@ impl LinearLayout {
         }
 
         for c in &self.children {
-            for i in 0..self.height() {
-                let line = match c.borrow_mut().render_lines().get(i) {
-                    None => String::from(""),
-                    Some(l) => l.to_string()
-                };
-
-                lines[i] += line.as_str();
+            let child = c.borrow();
+            let child_lines = child.render_lines();
+            for (i, line) in lines.iter_mut().enumerate() {
+                match child_lines.get(i).filter(|_| i < child.height()) {
+                    Some(l) => *line += l.as_str(),
+                    None => *line += &" ".repeat(child.width()) // Keep the children after a shorter one lined up
+                }
             }
         };
 
+        // Pad by what shows on screen - lines may be full of escape sequences
         lines.iter().
-            map(|line| (&*format!("{:width$}", line, width = self.width())).to_string()).
+            map(|line| format!("{}{:pad$}", line, "", pad = self.width().saturating_sub(display_width(line)))).
             collect::<Vec<String>>().
             join("\n")
     }
 
+    /// The constraint of a child in the direction we're stacking them.
+    fn along(&self, constraints: (Dim, Dim)) -> Dim {
+        match self.orientation {
+            Orientation::HORIZONTAL => constraints.0,
+            Orientation::VERTICAL => constraints.1
+        }
+    }
+
+    /// What a child with `constraints` is offered, with `remaining` of our `full` size left - its percentage
+    /// of our size, or all of it, as far as what's left goes.
+    fn offer(&self, constraints: (Dim, Dim), full: CharDims, remaining: CharDims) -> CharDims {
+        match self.orientation {
+            Orientation::HORIZONTAL => (min(constraints.0.offer(full.0), remaining.0), constraints.1.offer(full.1)),
+            Orientation::VERTICAL => (constraints.0.offer(full.0), min(constraints.1.offer(full.1), remaining.1))
+        }
+    }
+
     fn update_child_dims(orientation: Orientation, childrens_desired_dims: CharDims, child_dims: CharDims) -> CharDims {
         // Sum our children in the direction we are stacking them.
         // Capture the maximum in the direction we are stretching.
@@ -75,10 +93,10 @@ impl LinearLayout {
         match orientation {
             Orientation::VERTICAL => {
                 (remaining_parent_dims.0,
-                 if remaining_parent_dims.1 >= child_dims.1 { remaining_parent_dims.1 - child_dims.1 } else { 0 })
+                 remaining_parent_dims.1.saturating_sub(child_dims.1))
             }
             Orientation::HORIZONTAL => {
-                (if remaining_parent_dims.0 >= child_dims.0 { remaining_parent_dims.0 - child_dims.0 } else { 0 },
+                (remaining_parent_dims.0.saturating_sub(child_dims.0),
                  remaining_parent_dims.1)
             }
         }
@@ -99,35 +117,52 @@ impl View for LinearLayout {
         self.dims.size = (desired_size(&most_restrictive_width),
                           desired_size(&most_restrictive_height));
 
-        let mut remaining_parent_dims = self.dims.size.clone();
+        let full = self.dims.size;
+        let mut remaining_parent_dims = full;
+
+        // Weighted children share out what's left, once the others have taken what they need
+        let weights: Vec<usize> = self.children.iter().map(|c| match self.along(c.borrow().constraints()) {
+            Dim::Weight(w) => w,
+            _ => 0
+        }).collect();
 
-        for v in &mut self.children {
-            let child_dims = v.borrow_mut().inflate(&remaining_parent_dims);
+        for (v, _) in self.children.iter().zip(&weights).filter(|(_, w)| **w == 0) {
+            let offer = self.offer(v.borrow().constraints(), full, remaining_parent_dims);
+            let child_dims = v.borrow_mut().inflate(&offer);
             childrens_desired_dims = LinearLayout::update_child_dims(self.orientation, childrens_desired_dims, child_dims);
             remaining_parent_dims = LinearLayout::update_parent_dims(self.orientation, remaining_parent_dims, child_dims);
         }
 
-        let new_most_restrictive_width = min(Dim::Fixed(childrens_desired_dims.0), most_restrictive_width);
-        let new_most_restrictive_height = min(Dim::Fixed(childrens_desired_dims.1), most_restrictive_height);
-
-        self.dims.size.0 = match new_most_restrictive_width {
-            Dim::Fixed(n) => n,
-            Dim::UpTo(n) => n,
-            Dim::WrapContent => 0 // Only happens if we're a "WrapContent" and have 0 or empty children
+        let mut weight_left: usize = weights.iter().sum();
+        let mut space_left = match self.orientation {
+            Orientation::HORIZONTAL => remaining_parent_dims.0,
+            Orientation::VERTICAL => remaining_parent_dims.1
         };
+        for (v, w) in self.children.iter().zip(&weights).filter(|(_, w)| **w > 0) {
+            // Out of what's left rather than the whole, so the last one takes up any rounding
+            let share = space_left * w / weight_left;
+            space_left -= share;
+            weight_left -= w;
+
+            let offer = self.offer(v.borrow().constraints(), full, remaining_parent_dims);
+            let offer = match self.orientation {
+                Orientation::HORIZONTAL => (share, offer.1),
+                Orientation::VERTICAL => (offer.0, share)
+            };
+            let child_dims = v.borrow_mut().inflate(&offer);
+            childrens_desired_dims = LinearLayout::update_child_dims(self.orientation, childrens_desired_dims, child_dims);
+        }
 
-        self.dims.size.1 = match new_most_restrictive_height {
-            Dim::Fixed(n) => n,
-            Dim::UpTo(n) => n,
-            Dim::WrapContent => 0 // Only happens if we're a "WrapContent" and have 0 or empty children
-        };
+        // If we're a "WrapContent" with no children, or only empty ones, we come to nothing
+        self.dims.size = (content_size(self.dims.width_constraint, childrens_desired_dims.0, parent_dimensions.0),
+                          content_size(self.dims.height_constraint, childrens_desired_dims.1, parent_dimensions.1));
 
         if self.height() == 0 {
             info!("LL {:?} Dimensions: {}x{}", self.orientation, self.width(), self.height());
             info!("LL zero height child dims height: {}; constraint: {:?}", childrens_desired_dims.1, self.dims.height_constraint);
         }
 
-        self.dims.size.clone()
+        self.dims.size
     }
 
     fn constraints(&self) -> (Dim, Dim) { (self.dims.width_constraint, self.dims.height_constraint) }
@@ -148,6 +183,20 @@ impl View for LinearLayout {
     fn render_lines(&self) -> Vec<String> {
         self.render().split("\n").map(|c| c.to_string()).collect()
     }
+
+    fn regions(&self) -> Vec<Region> {
+        let mut regions = vec![];
+        let mut offset = (0, 0);
+        for child in &self.children {
+            let child = child.borrow();
+            regions.extend(child.regions().into_iter().map(|r| r.offset(offset)));
+            offset = match self.orientation {
+                Orientation::HORIZONTAL => (offset.0 + child.width(), 0),
+                Orientation::VERTICAL => (0, offset.1 + child.height())
+            };
+        }
+        regions
+    }
 }
 
 
@@ -260,6 +309,100 @@ mod tests {
         assert_eq!("This is soThis is soThis is so\nwith multiwith multiwith multi".to_string(), ll.render());
     }
 
+    #[test]
+    fn horz_rendering_keeps_styles_inside_their_child() {
+        let mut colored = TextView::new(Dim::Fixed(3), Dim::WrapContent);
+        colored.update_content("\x1b[31mred and\nmore".to_string());
+        let mut plain = TextView::new(Dim::WrapContent, Dim::WrapContent);
+        plain.update_content("one\ntwo\nthree".to_string());
+
+        let mut ll = horz_ll_with_wrap_content();
+        ll.add_child(Rc::new(RefCell::new(colored)));
+        ll.add_child(Rc::new(RefCell::new(plain)));
+        ll.inflate(&(100, 100));
+
+        assert_eq!(vec!["\x1b[31mred\x1b[0mone  ", "\x1b[31mmor\x1b[0mtwo  ", "   three"], ll.render_lines());
+    }
+
+    fn text_widget(width: Dim, height: Dim, text: &str) -> Rc<RefCell<TextView>> {
+        let mut tw = TextView::new(width, height);
+        tw.raw_text = String::from(text);
+        Rc::new(RefCell::new(tw))
+    }
+
+    #[test]
+    fn weighted_children_share_what_the_others_leave() {
+        let fixed = text_widget(Dim::Fixed(10), Dim::WrapContent, "0123456789");
+        let one = text_widget(Dim::Weight(1), Dim::WrapContent, "a");
+        let two = text_widget(Dim::Weight(2), Dim::WrapContent, "b");
+
+        let mut ll = LinearLayout::new(Orientation::HORIZONTAL, Dim::Fixed(30), Dim::WrapContent);
+        ll.add_child(one.clone());
+        ll.add_child(fixed.clone());
+        ll.add_child(two.clone());
+        ll.inflate(&(100, 100));
+
+        assert_eq!((6, 10, 14), (one.borrow().width(), fixed.borrow().width(), two.borrow().width()));
+        assert_eq!((30, 1), (ll.width(), ll.height()));
+        assert_eq!(vec!["a     0123456789b             "], ll.render_lines());
+    }
+
+    #[test]
+    fn weighted_children_fill_across_the_layout() {
+        let filler = text_widget(Dim::Weight(1), Dim::Weight(1), "a");
+        let mut ll = LinearLayout::new(Orientation::VERTICAL, Dim::WrapContent, Dim::WrapContent);
+        ll.add_child(filler.clone());
+        ll.inflate(&(8, 3));
+        assert_eq!((8, 3), (filler.borrow().width(), filler.borrow().height()));
+    }
+
+    #[test]
+    fn percentages_are_of_the_whole_layout() {
+        let quarter = text_widget(Dim::Percent(50), Dim::Percent(25), "a");
+        let rest = text_widget(Dim::WrapContent, Dim::Weight(1), "b");
+        let mut ll = LinearLayout::new(Orientation::VERTICAL, Dim::Fixed(10), Dim::Fixed(20));
+        ll.add_child(rest.clone());
+        ll.add_child(quarter.clone());
+        ll.inflate(&(100, 100));
+
+        assert_eq!((5, 5), (quarter.borrow().width(), quarter.borrow().height()));
+        assert_eq!(15, rest.borrow().height());
+    }
+
+    #[test]
+    fn percentages_only_get_what_is_left() {
+        let big = text_widget(Dim::Percent(80), Dim::WrapContent, "a");
+        let mut ll = LinearLayout::new(Orientation::HORIZONTAL, Dim::Fixed(10), Dim::WrapContent);
+        ll.add_child(text_widget(Dim::Fixed(5), Dim::WrapContent, "12345"));
+        ll.add_child(big.clone());
+        ll.inflate(&(100, 100));
+        assert_eq!(5, big.borrow().width());
+    }
+
+    #[test]
+    fn regions_are_where_each_child_was_laid_out() {
+        let mut left = fixed_size_text_widget();
+        left.id("left");
+        let mut top = fixed_size_text_widget();
+        top.id("top");
+        let mut bottom = wrap_content_text_widget();
+        bottom.id("bottom");
+
+        let mut column = vert_ll_with_wrap_content();
+        column.add_child(Rc::new(RefCell::new(top)));
+        column.add_child(Rc::new(RefCell::new(bottom)));
+        let mut ll = horz_ll_with_wrap_content();
+        ll.add_child(Rc::new(RefCell::new(left)));
+        ll.add_child(Rc::new(RefCell::new(column)));
+        ll.inflate(&(100, 100));
+
+        assert_eq!(ll.regions(), vec![
+            Region { id: "left".to_string(), origin: (0, 0), size: (10, 2) },
+            Region { id: "top".to_string(), origin: (10, 0), size: (10, 2) },
+            Region { id: "bottom".to_string(), origin: (10, 2), size: (22, 3) },
+        ]);
+    }
+
     #[test]
     fn when_invisible_renders_nothing() {
         let mut ll = vert_ll_with_fixed_size();
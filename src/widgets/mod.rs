@@ -1,38 +1,76 @@
-use std::cmp::{Ordering, min};
+use std::cmp::{max, min, Ordering};
 use std::rc::Rc;
 use std::cell::RefCell;
-use crate::crossterm_backend::find_vt100s;
 
+mod border;
+mod frame;
+mod grid;
 mod linear_layout;
+mod screen;
+mod styled;
+mod tabs;
 mod text_view;
 
+pub use screen::Screen;
+pub use styled::{display_width, grapheme_width, Color, Style, StyledLine};
+
 /***
 Dim: Represents a constraint on layout.
-    WrapContent -> Takes its size from the size of its children.
-    Fixed(n)    -> Always 'n' characters, until the limits of the container or terminal get in the way.
-    UpTo(n)     -> Resizes based on content between 0 and n characters.
+    WrapContent   -> Takes its size from the size of its children.
+    Fixed(n)      -> Always 'n' characters, until the limits of the container or terminal get in the way.
+    UpTo(n)       -> Resizes based on content between 0 and n characters.
+    Between(m, n) -> Resizes based on content, but never less than m characters or more than n.
+    Percent(p)    -> p% of the container it's in.
+    Weight(w)     -> Fills the container. Beside others in a LinearLayout, it gets w shares of the space left over
+                     once they've taken what they need - Ex: a Weight(2) gets twice what a Weight(1) does.
+    Percent and Weight views fill whatever their container offers them - it works out how much that is.
  */
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum Dim {
     WrapContent,
     Fixed(usize),
     UpTo(usize),
-    // Between(usize, usize),
+    Between(usize, usize),
+    Percent(usize),
+    Weight(usize),
 }
 
 impl Dim {
-    fn to_ord(&self) -> usize {
+    fn to_ord(self) -> usize {
+        match self {
+            Dim::UpTo(x) => x,
+            Dim::Fixed(x) => x,
+            Dim::Between(_, x) => x,
+            Dim::WrapContent | Dim::Percent(_) | Dim::Weight(_) => 1_000_000_000,
+        }
+    }
+
+    /// Whether the view fills what it's offered, rather than sizing itself to its content.
+    pub fn fills(self) -> bool {
+        matches!(self, Dim::Percent(_) | Dim::Weight(_))
+    }
+
+    /// The least a view can be, however little content it has.
+    pub fn least(self) -> usize {
+        match self {
+            Dim::Between(least, _) => least,
+            _ => 0
+        }
+    }
+
+    /// How much a view's offered, when its container has `room` - its percentage of it, or all of it.
+    /// A LinearLayout offers weighted views less, once it's shared what's left between them.
+    pub fn offer(self, room: usize) -> usize {
         match self {
-            Dim::UpTo(x) => *x,
-            Dim::Fixed(x) => *x,
-            Dim::WrapContent => 1_000_000_000,
+            Dim::Percent(p) => room * p / 100,
+            _ => room
         }
     }
 }
 
 impl PartialOrd for Dim {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.to_ord().cmp(&other.to_ord()))
+        Some(self.cmp(other))
     }
 }
 
@@ -46,81 +84,125 @@ View: A trait representing a render-able text widget.
  */
 pub trait View {
     fn inflate(&mut self, parent_size: &CharDims) -> CharDims;
+    #[allow(dead_code)]
     fn constraints(&self) -> (Dim, Dim);
     fn width(&self) -> usize;
     fn height(&self) -> usize;
     fn render(&self) -> String;
     fn render_lines(&self) -> Vec<String>;
+
+    /// Whether the view has the focus - or for a container, whether something in it does.
+    fn focused(&self) -> bool { false }
+
+    /// What the view's showing, for a border around it to say.
+    fn title(&self) -> Option<String> { None }
+
+    /// A line about how what the view's showing came about - Ex: when it ran - for a border around it to say.
+    fn footer(&self) -> Option<String> { None }
+
+    /// Where the TextViews in this view are, relative to its top-left corner. Only those which show up.
+    fn regions(&self) -> Vec<Region> { vec![] }
+}
+
+/***
+Region: Where a TextView is - the id it was given, its top-left corner and its size.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub id: String,
+    pub origin: CharDims,
+    pub size: CharDims,
+}
+
+impl Region {
+    /// The same region, in a container where the view it's in starts at `by`.
+    pub fn offset(self, by: CharDims) -> Region {
+        Region { origin: (self.origin.0 + by.0, self.origin.1 + by.1), ..self }
+    }
 }
 
 /***
 TextView: A simple text container. Throw a String at it.
+    With a screen, it's a terminal instead - text written to it is interpreted by a virtual Screen the size of the view.
  */
 pub struct TextView {
+    id: Option<String>,
     raw_text: String,
+    screen: Option<Screen>,
+    failure: Option<String>,
+    status: Option<String>,
+    title: Option<String>,
+    footer: Option<String>,
+    follow: bool,
+    scroll: usize,
+    tail: bool,
+    focused: bool,
+    overflow: Overflow,
     dims: Dimensions,
+    room: CharDims,
     formatter: Box<dyn TextFormatter>,
     visible: bool
 }
 
 /***
-TextFormatter: A trait for classes that convert from a raw string into a formatted one.
+TextFormatter: A trait for classes that convert a styled line into a formatted string, exactly as wide as asked.
     Generic in order to allow different Terminal backends to use their own custom
     String-variants.
  */
 pub trait TextFormatter {
-    fn format(&self, s: String, max_len: usize) -> String;
+    fn format(&self, line: &StyledLine, width: usize) -> String;
 }
 
+#[allow(dead_code)]
 struct DumbFormatter{}
 
 impl TextFormatter for DumbFormatter {
-    fn format(&self, s: String, n: usize) -> String {
-        let last_len = min(n, s.len());
-        s[0..last_len].to_string()
+    fn format(&self, line: &StyledLine, width: usize) -> String {
+        StyledLine::plain(&line.text()).render(width)
     }
 }
 
 struct Vt100Formatter{}
 
 impl TextFormatter for Vt100Formatter {
-    fn format(&self, s: String, n: usize) -> String {
-        if n >= s.len() { return format!("{:width$}", s, width = n) }
-
-        let vt100s = find_vt100s(s.as_str());
-        if vt100s.last().is_none() { return s[0..n].to_string(); }
-        let mut captured_chars = 0;
-        let mut end = 0;
-
-        for c in vt100s.iter() {
-            if (captured_chars + 1) < n {  // cc+1 to avoid subtraction with overflow
-                let next_block_of_text_size = c.start() - end;
-                let next_incr = if captured_chars + next_block_of_text_size >= n {
-                    (captured_chars + next_block_of_text_size) - n
-                } else {
-                    next_block_of_text_size
-                };
-
-                captured_chars += next_incr;
-                end = c.end();
-            }
-        };
-
-        if captured_chars < n {
-            end += n - captured_chars; // grab any remaining characters we need
-        }
+    fn format(&self, line: &StyledLine, width: usize) -> String {
+        line.render(width)
+    }
+}
 
-        // info!("Str {} chars\n----\n{}\n----", s.len(), s);
-        // info!("Slice:\n-----\n{}\n------", s[0..end].to_string());
+/***
+Overflow: What a TextView does with lines too long for it.
+    Truncate  -> Cuts them off at the edge. The default.
+    Wrap      -> Carries them on to the next line, breaking wherever they reach the edge
+    WrapWords -> Carries them on to the next line, breaking between words where possible
+    Ellipsis  -> Cuts them off, ending them with '…' so you can tell
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Overflow {
+    #[default]
+    Truncate,
+    Wrap,
+    WrapWords,
+    Ellipsis,
+}
 
-        let slice_end = min(s.len(), end);
-        format!("{:width$}", s[0..slice_end].to_string(), width = end)
-    }
+/***
+Scroll: A move through content too long to show all at once.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scroll {
+    Up(usize),
+    Down(usize),
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
 }
 
 /***
 Orientation: For a LinearLayout. You know what this does.
  */
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Copy, Clone, Debug)]
 pub enum Orientation {
     HORIZONTAL,
@@ -137,6 +219,94 @@ pub struct LinearLayout {
     visible: bool
 }
 
+/***
+Border: Draws a line around another View, with the view's title set into the top of it and its footer the bottom.
+    It lights up when the view inside has the focus.
+ */
+pub struct Border {
+    child: Rc<RefCell<dyn View>>,
+    style: BorderStyle,
+    size: CharDims
+}
+
+/***
+BorderStyle: The lines a Border is drawn with.
+    Single  -> ┌─┐
+    Double  -> ╔═╗
+    Rounded -> ╭─╮
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BorderStyle {
+    Single,
+    Double,
+    Rounded,
+}
+
+/***
+Frame: Leaves space around another View, and puts the view where it's asked to within the rest.
+    The frame's sized by its own constraints - it's up to the view inside to be smaller, so there's somewhere to
+    move it to.
+ */
+pub struct Frame {
+    child: Rc<RefCell<dyn View>>,
+    space: usize,
+    align: Align,
+    valign: Align,
+    offset: CharDims,
+    dims: Dimensions,
+}
+
+/***
+Align: Where a view goes in a Frame with more room than it needs - across, or down.
+    Start  -> Left, or the top. The default.
+    Center -> In the middle.
+    End    -> Right, or the bottom.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Start,
+    Center,
+    End,
+}
+
+/***
+Grid: Lays child View widgets out in rows and columns, which line up however much each child has in it.
+    Each track (row or column) is a Dim - Fixed, a Percent of the grid, a Weight of what's left once the rest have
+    what they need, or WrapContent to fit the biggest child in it. Children can span several tracks, with the gaps
+    between them.
+ */
+pub struct Grid {
+    columns: Vec<Dim>,
+    rows: Vec<Dim>,
+    gap: CharDims,
+    children: Vec<(GridCell, Rc<RefCell<dyn View>>)>,
+    // Where each child is, and the space it has, once inflated
+    placed: Vec<(CharDims, CharDims)>,
+    dims: Dimensions,
+}
+
+/***
+GridCell: Where a child of a Grid goes - the row and column of its top-left corner, and how many of each it spans.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GridCell {
+    pub row: usize,
+    pub column: usize,
+    pub row_span: usize,
+    pub column_span: usize,
+}
+
+/***
+Tabs: Shows one of several child View widgets at a time - its page - under a bar with every page's title in it.
+    The pages which aren't showing aren't inflated or rendered.
+ */
+pub struct Tabs {
+    pages: Vec<(String, Rc<RefCell<dyn View>>)>,
+    current: usize,
+    dims: Dimensions,
+}
+
 /***
 Dimensions: An internal struct used to track the constraints and actual size of a View
  */
@@ -163,10 +333,19 @@ pub fn desired_size(constraint: &Dim) -> usize {
     match constraint {
         Dim::WrapContent => 0, // If the constraint at this point is wrap content, we have to inflate children to see
         Dim::Fixed(x) => *x,
-        Dim::UpTo(x) => *x
+        Dim::UpTo(x) => *x,
+        Dim::Between(_, x) => *x,
+        Dim::Percent(_) | Dim::Weight(_) => 0 // Should have been capped by what the container offered by now
     }
 }
 
+/// How big a view sized by its content is - `content`, within its constraint and what it's offered.
+pub fn content_size(constraint: Dim, content: usize, offered: usize) -> usize {
+    if constraint.fills() { return offered; }
+    let most_restrictive = min(Dim::UpTo(content), min(constraint, Dim::Fixed(offered)));
+    max(desired_size(&most_restrictive), constraint.least().min(offered))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,12 +357,13 @@ mod tests {
         assert!(Dim::Fixed(1).to_ord() == Dim::UpTo(1).to_ord());
         assert!(Dim::Fixed(1) < Dim::UpTo(2));
         assert!(Dim::Fixed(1000) < Dim::WrapContent);
+        assert!(Dim::Between(5, 10) < Dim::Fixed(11));
     }
 
     #[test]
     fn slicing_vt100_string_works() {
         let fmt = Vt100Formatter{};
-        let fmt_str = fmt.format(VT100_TEST.to_string(), 2);
-        assert_eq!("T\u{1B}[33mE", fmt_str);
+        let fmt_str = fmt.format(&StyledLine::parse(VT100_TEST)[0], 2);
+        assert_eq!("T\u{1B}[33mE\u{1B}[0m", fmt_str);
     }
 }
\ No newline at end of file
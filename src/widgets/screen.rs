@@ -0,0 +1,495 @@
+use std::cmp::min;
+use std::mem;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::widgets::{grapheme_width, CharDims, Style, StyledLine};
+
+/***
+Cell: One column of a Screen - a character, with any accents combined with it - and the style it was drawn in.
+    Wide characters take up two cells. The second is left empty.
+ */
+#[derive(Clone, Debug, PartialEq)]
+struct Cell {
+    text: String,
+    style: Style,
+}
+
+impl Cell {
+    fn blank(style: Style) -> Cell {
+        Cell { text: " ".to_string(), style }
+    }
+
+    fn is_wide(&self) -> bool {
+        grapheme_width(&self.text) == 2
+    }
+}
+
+/***
+Parser: Where we are in the output, as far as escape sequences go. Sequences can be split between
+    writes, so this carries over from one to the next.
+    Ground  -> Plain text
+    Escape  -> Just seen ESC
+    Charset -> ESC ( or similar, which picks a character set. We skip the character naming it.
+    Csi     -> Inside ESC [, collecting parameters until the final character
+    Osc     -> Inside ESC ], which sets titles and the like. Ends with BEL, or ESC \ (true once we've seen the ESC).
+ */
+#[derive(Clone, Debug, PartialEq)]
+enum Parser {
+    Ground,
+    Escape,
+    Charset,
+    Csi(String),
+    Osc(bool),
+}
+
+/***
+Screen: A virtual terminal. Output written to it moves the cursor, erases and styles text the way it would
+    on a real (VT100/xterm-ish) terminal, and the resulting grid of characters is what gets rendered.
+    Lines scrolled off the top are gone - like a terminal without scrollback.
+ */
+#[derive(Clone, Debug)]
+pub struct Screen {
+    cells: Vec<Vec<Cell>>,
+    size: CharDims,
+    cursor: CharDims,
+    saved_cursor: CharDims,
+    // The next character wraps onto the next line first. Writing the last column doesn't wrap until then.
+    wrap_pending: bool,
+    // The rows which scroll - top and bottom, inclusive
+    scroll_region: (usize, usize),
+    pen: Style,
+    parser: Parser,
+}
+
+impl Screen {
+    pub fn new(size: CharDims) -> Screen {
+        Screen {
+            cells: vec![vec![Cell::blank(Style::default()); size.0]; size.1],
+            size,
+            cursor: (0, 0),
+            saved_cursor: (0, 0),
+            wrap_pending: false,
+            scroll_region: (0, size.1.saturating_sub(1)),
+            pen: Style::default(),
+            parser: Parser::Ground,
+        }
+    }
+
+    /// Start again with a blank screen, as if a new command had been started in it.
+    pub fn reset(&mut self) {
+        *self = Screen::new(self.size);
+    }
+
+    /// (columns, rows)
+    pub fn size(&self) -> CharDims {
+        self.size
+    }
+
+    /// Change size, keeping what's on the screen. Rows and columns which no longer fit are dropped from the bottom and right.
+    pub fn resize(&mut self, size: CharDims) {
+        self.cells.resize(size.1, vec![Cell::blank(Style::default()); size.0]);
+        for row in &mut self.cells { row.resize(size.0, Cell::blank(Style::default())); }
+
+        self.size = size;
+        self.scroll_region = (0, size.1.saturating_sub(1));
+        self.cursor = (min(self.cursor.0, size.0.saturating_sub(1)), min(self.cursor.1, size.1.saturating_sub(1)));
+        self.wrap_pending = false;
+    }
+
+    pub fn write(&mut self, output: &str) {
+        for c in output.chars() {
+            let parser = mem::replace(&mut self.parser, Parser::Ground);
+            self.parser = match parser {
+                Parser::Ground => self.ground(c),
+                Parser::Escape => self.escape(c),
+                Parser::Charset => Parser::Ground,
+                Parser::Csi(mut params) => match c {
+                    '\x40'..='\x7e' => { self.csi(&params, c); Parser::Ground },
+                    '\x1b' => Parser::Escape, // Abandoned - start again
+                    _ => { params.push(c); Parser::Csi(params) }
+                },
+                Parser::Osc(escaped) => match c {
+                    '\x07' => Parser::Ground,
+                    '\\' if escaped => Parser::Ground,
+                    _ => Parser::Osc(c == '\x1b'),
+                },
+            };
+        }
+    }
+
+    /// One row of the screen, as wide as the screen. Halves of wide characters which have been
+    /// partly overwritten show as blanks.
+    pub fn line(&self, row: usize) -> StyledLine {
+        let mut line = StyledLine::default();
+        let cells = self.cells.get(row).map(Vec::as_slice).unwrap_or_default();
+
+        for (col, cell) in cells.iter().enumerate() {
+            let first_half = cell.is_wide() && cells.get(col + 1).is_some_and(|next| next.text.is_empty());
+            let second_half = cell.text.is_empty() && col > 0 && cells[col - 1].is_wide();
+            match (cell.is_wide() || cell.text.is_empty(), first_half || second_half) {
+                (true, true) => line.push_str(&cell.text, cell.style),
+                (true, false) => line.push(' ', cell.style),
+                (false, _) => line.push_str(&cell.text, cell.style),
+            }
+        }
+        line
+    }
+
+    fn ground(&mut self, c: char) -> Parser {
+        match c {
+            '\x1b' => return Parser::Escape,
+            '\r' => self.carriage_return(),
+            // We don't ask the pty to turn "\n" into "\r\n", so a line feed starts a new line as well
+            '\n' | '\x0b' | '\x0c' => { self.carriage_return(); self.line_feed(); },
+            '\x08' => { self.cursor.0 = self.cursor.0.saturating_sub(1); self.wrap_pending = false; },
+            '\t' => self.move_to((self.cursor.0 / 8 + 1) * 8, self.cursor.1),
+            c if c.is_control() => {}, // Bells and the like
+            c => self.print(c),
+        }
+        Parser::Ground
+    }
+
+    fn escape(&mut self, c: char) -> Parser {
+        match c {
+            '[' => return Parser::Csi(String::new()),
+            ']' => return Parser::Osc(false),
+            '(' | ')' | '*' | '+' | '#' => return Parser::Charset,
+            '7' => self.saved_cursor = self.cursor,
+            '8' => self.move_to(self.saved_cursor.0, self.saved_cursor.1),
+            'D' => self.line_feed(),
+            'E' => { self.carriage_return(); self.line_feed(); },
+            'M' => self.reverse_line_feed(),
+            'c' => self.reset(),
+            _ => {}
+        }
+        Parser::Ground
+    }
+
+    fn csi(&mut self, params: &str, command: char) {
+        if let Some(private) = params.strip_prefix('?') {
+            return self.private_mode(private, command);
+        }
+
+        // Other private markers and intermediate bytes mean something else altogether - Ex: "CSI > 4 ; 2 m" turns on
+        // xterm's modifyOtherKeys, rather than styling the text
+        if params.starts_with(['<', '=', '>']) || params.contains(|c: char| ('\x20'..='\x2f').contains(&c)) {
+            return;
+        }
+
+        let args: Vec<usize> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+        // Most parameters are counts or positions, where leaving them out (or 0) means 1
+        let n = args[0].max(1);
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0).max(1);
+        let (col, row) = self.cursor;
+        // Scrolling more lines than there are in the region is the same as scrolling all of them
+        let lines = min(n, self.scroll_region.1.saturating_sub(self.scroll_region.0) + 1);
+
+        match command {
+            'A' => self.move_to(col, row.saturating_sub(n).max(self.scroll_top(row))),
+            'B' | 'e' => self.move_to(col, min(row.saturating_add(n), self.scroll_bottom(row))),
+            'C' | 'a' => self.move_to(col.saturating_add(n), row),
+            'D' => self.move_to(col.saturating_sub(n), row),
+            'E' => self.move_to(0, min(row.saturating_add(n), self.scroll_bottom(row))),
+            'F' => self.move_to(0, row.saturating_sub(n).max(self.scroll_top(row))),
+            'G' | '`' => self.move_to(n - 1, row),
+            'd' => self.move_to(col, n - 1),
+            'H' | 'f' => self.move_to(arg(1) - 1, n - 1),
+            'J' => self.erase_display(args[0]),
+            'K' => self.erase_line(args[0]),
+            'X' => self.erase(row, col, col.saturating_add(n)),
+            '@' => self.insert_cells(n),
+            'P' => self.delete_cells(n),
+            'L' => self.insert_lines(lines),
+            'M' => self.delete_lines(lines),
+            'S' => for _ in 0..lines { self.scroll_up(self.scroll_region.0, self.scroll_region.1) },
+            'T' => for _ in 0..lines { self.scroll_down(self.scroll_region.0, self.scroll_region.1) },
+            'm' => self.pen.apply_sgr(params),
+            'r' => self.set_scroll_region(args[0], args.get(1).copied().unwrap_or(0)),
+            's' => self.saved_cursor = self.cursor,
+            'u' => self.move_to(self.saved_cursor.0, self.saved_cursor.1),
+            _ => {} // Modes, reports and the like, which don't change what's on the screen
+        }
+    }
+
+    /// DEC private modes. Only switching to and from the alternate screen changes what's shown - we clear it either way.
+    fn private_mode(&mut self, params: &str, command: char) {
+        let alternate_screen = params.split(';').any(|p| p == "47" || p == "1047" || p == "1049");
+        if alternate_screen && (command == 'h' || command == 'l') {
+            self.erase_display(2);
+            if command == 'h' { self.move_to(0, 0); }
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        let width = c.width().unwrap_or(0);
+        if width == 0 { return self.combine(c); }
+        if width > self.size.0 || self.size.1 == 0 { return; }
+
+        // A wide character which won't fit on the end of the line goes on the next one
+        if self.wrap_pending || self.cursor.0 + width > self.size.0 {
+            self.carriage_return();
+            self.line_feed();
+        }
+
+        let (col, row) = self.cursor;
+        self.overwrite(row, col);
+        self.cells[row][col] = Cell { text: c.to_string(), style: self.pen };
+        if width == 2 {
+            self.overwrite(row, col + 1);
+            self.cells[row][col + 1] = Cell { text: String::new(), style: self.pen };
+        }
+
+        if col + width < self.size.0 {
+            self.cursor.0 += width;
+        } else {
+            self.cursor.0 = self.size.0 - 1;
+            self.wrap_pending = true;
+        }
+    }
+
+    /// Add an accent (or other zero-width character) to the last character written.
+    fn combine(&mut self, c: char) {
+        let (col, row) = self.cursor;
+        let mut target = if self.wrap_pending { col } else { match col.checked_sub(1) { Some(col) => col, None => return } };
+        if let Some(cells) = self.cells.get_mut(row) {
+            if cells[target].text.is_empty() && target > 0 { target -= 1; }
+            cells[target].text.push(c);
+        }
+    }
+
+    /// About to write over a cell - if it's half of a wide character, blank the other half.
+    fn overwrite(&mut self, row: usize, col: usize) {
+        let blank = self.blank();
+        let cells = &mut self.cells[row];
+        if cells[col].text.is_empty() && col > 0 { cells[col - 1] = blank.clone(); }
+        if cells.get(col + 1).is_some_and(|next| next.text.is_empty()) { cells[col + 1] = blank; }
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        self.cursor = (min(col, self.size.0.saturating_sub(1)), min(row, self.size.1.saturating_sub(1)));
+        self.wrap_pending = false;
+    }
+
+    fn carriage_return(&mut self) {
+        self.cursor.0 = 0;
+        self.wrap_pending = false;
+    }
+
+    fn line_feed(&mut self) {
+        let (top, bottom) = self.scroll_region;
+        match self.cursor.1 {
+            row if row == bottom => self.scroll_up(top, bottom),
+            row if row + 1 < self.size.1 => self.cursor.1 += 1,
+            _ => {}
+        }
+        self.wrap_pending = false;
+    }
+
+    fn reverse_line_feed(&mut self) {
+        let (top, bottom) = self.scroll_region;
+        match self.cursor.1 {
+            row if row == top => self.scroll_down(top, bottom),
+            row => self.cursor.1 = row.saturating_sub(1),
+        }
+        self.wrap_pending = false;
+    }
+
+    // Cursor movement stops at the edge of the scroll region - if it started inside it
+    fn scroll_top(&self, row: usize) -> usize {
+        if row >= self.scroll_region.0 { self.scroll_region.0 } else { 0 }
+    }
+
+    fn scroll_bottom(&self, row: usize) -> usize {
+        if row <= self.scroll_region.1 { self.scroll_region.1 } else { self.size.1.saturating_sub(1) }
+    }
+
+    fn scroll_up(&mut self, top: usize, bottom: usize) {
+        if bottom >= self.cells.len() || top > bottom { return; }
+        self.cells.remove(top);
+        self.cells.insert(bottom, vec![self.blank(); self.size.0]);
+    }
+
+    fn scroll_down(&mut self, top: usize, bottom: usize) {
+        if bottom >= self.cells.len() || top > bottom { return; }
+        self.cells.remove(bottom);
+        self.cells.insert(top, vec![self.blank(); self.size.0]);
+    }
+
+    fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let last = self.size.1.saturating_sub(1);
+        let (top, bottom) = (top.saturating_sub(1), if bottom == 0 { last } else { min(bottom - 1, last) });
+        if top < bottom {
+            self.scroll_region = (top, bottom);
+            self.move_to(0, 0);
+        }
+    }
+
+    /// What erased cells are filled with. They keep the pen's background colour, like xterm.
+    fn blank(&self) -> Cell {
+        Cell::blank(Style { bg: self.pen.bg, ..Style::default() })
+    }
+
+    /// Blank columns `from` up to (but not including) `to` of a row.
+    fn erase(&mut self, row: usize, from: usize, to: usize) {
+        let blank = self.blank();
+        if let Some(cells) = self.cells.get_mut(row) {
+            let to = min(to, cells.len());
+            for cell in cells.iter_mut().take(to).skip(from) { *cell = blank.clone(); }
+        }
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        let (col, row) = self.cursor;
+        match mode {
+            0 => self.erase(row, col, self.size.0),
+            1 => self.erase(row, 0, col + 1),
+            _ => self.erase(row, 0, self.size.0),
+        }
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        let (col, row) = self.cursor;
+        let rows = match mode {
+            0 => { self.erase(row, col, self.size.0); row + 1..self.size.1 },
+            1 => { self.erase(row, 0, col + 1); 0..row },
+            _ => 0..self.size.1,
+        };
+        for r in rows { self.erase(r, 0, self.size.0); }
+    }
+
+    fn insert_cells(&mut self, n: usize) {
+        let (col, row) = self.cursor;
+        let blank = self.blank();
+        if let Some(cells) = self.cells.get_mut(row) {
+            for _ in 0..min(n, cells.len() - col) {
+                cells.insert(col, blank.clone());
+                cells.pop();
+            }
+        }
+    }
+
+    fn delete_cells(&mut self, n: usize) {
+        let (col, row) = self.cursor;
+        let blank = self.blank();
+        if let Some(cells) = self.cells.get_mut(row) {
+            for _ in 0..min(n, cells.len() - col) {
+                cells.remove(col);
+                cells.push(blank.clone());
+            }
+        }
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        let (top, bottom) = self.scroll_region;
+        let row = self.cursor.1;
+        if row < top || row > bottom { return; }
+        for _ in 0..n { self.scroll_down(row, bottom); }
+        self.carriage_return();
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        let (top, bottom) = self.scroll_region;
+        let row = self.cursor.1;
+        if row < top || row > bottom { return; }
+        for _ in 0..n { self.scroll_up(row, bottom); }
+        self.carriage_return();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(size: CharDims, output: &str) -> Screen {
+        let mut screen = Screen::new(size);
+        screen.write(output);
+        screen
+    }
+
+    impl Screen {
+        // Each row, without its trailing blanks
+        fn lines(&self) -> Vec<String> {
+            (0..self.size.1).map(|row| {
+                let used = self.cells[row].iter().rposition(|c| *c != Cell::blank(Style::default())).map_or(0, |col| col + 1);
+                self.line(row).render(used)
+            }).collect()
+        }
+    }
+
+    #[test]
+    fn carriage_returns_overwrite_the_line() {
+        assert_eq!(screen((10, 2), "50%\r100%\ndone").lines(), vec!["100%", "done"]);
+    }
+
+    #[test]
+    fn moves_the_cursor_around() {
+        assert_eq!(screen((10, 3), "\x1b[2;3Hx\x1b[Ay\x1b[2Bz\x1b[1;1H\x1b[Cw").lines(), vec![" w y", "  x", "    z"]);
+        assert_eq!(screen((5, 1), "abc\x1b[2Gd\x08\x08e").lines(), vec!["edc"]);
+    }
+
+    #[test]
+    fn erases_lines_and_the_screen() {
+        assert_eq!(screen((10, 2), "hello\x1b[3D\x1b[K").lines(), vec!["he", ""]);
+        assert_eq!(screen((10, 2), "hello\x1b[3D\x1b[1K").lines(), vec!["   lo", ""]);
+        assert_eq!(screen((10, 2), "one\ntwo\x1b[2J").lines(), vec!["", ""]);
+        assert_eq!(screen((10, 2), "one\ntwo\x1b[1;2H\x1b[J").lines(), vec!["o", ""]);
+    }
+
+    #[test]
+    fn scrolls_when_writing_past_the_bottom() {
+        assert_eq!(screen((5, 2), "one\ntwo\nthree").lines(), vec!["two", "three"]);
+        assert_eq!(screen((3, 2), "abcdefg").lines(), vec!["def", "g"]);
+        assert_eq!(screen((5, 3), "top\x1b[2;3r\x1b[2Hone\ntwo\nsix").lines(), vec!["top", "two", "six"]);
+    }
+
+    #[test]
+    fn huge_counts_are_no_more_than_the_whole_screen() {
+        let max = usize::MAX;
+        assert_eq!(screen((5, 3), "one\ntwo\x1b[999999999L").lines(), vec!["one", "", ""]);
+        assert_eq!(screen((5, 3), "one\ntwo\x1b[999999999S\x1b[999999999T").lines(), vec!["", "", ""]);
+        assert_eq!(screen((5, 2), &format!("a\x1b[{0}Cb\x1b[{0}Bc\x1b[1;1H\x1b[{0}X", max)).lines(), vec!["", "    c"]);
+    }
+
+    #[test]
+    fn styles_text_and_resets_at_the_end_of_each_line() {
+        let screen = screen((10, 2), "\x1b[1;31mred\x1b[0m plain\n\x1b[38;5;0mblack\x1b[m");
+        assert_eq!(screen.lines(), vec!["\x1b[1;31mred\x1b[0m plain", "\x1b[30mblack\x1b[0m"]);
+        assert_eq!(screen.line(0).render(4), "\x1b[1;31mred\x1b[0m ");
+
+        // Not SGR at all - vim asks for modifyOtherKeys like this when it starts
+        let screen = self::screen((10, 1), "\x1b[31m\x1b[>4;2mred\x1b[=1m\x1b[0 m");
+        assert_eq!(screen.lines(), vec!["\x1b[31mred\x1b[0m"]);
+    }
+
+    #[test]
+    fn escape_sequences_can_be_split_between_writes() {
+        let mut screen = Screen::new((10, 1));
+        screen.write("ab\x1b[");
+        screen.write("1Dc\x1b]0;title\x07");
+        assert_eq!(screen.lines(), vec!["ac"]);
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        assert_eq!(screen((5, 2), "日本語").lines(), vec!["日本", "語"]);
+        assert_eq!(screen((6, 1), "日本\x1b[2Gx").lines(), vec![" x本"]);
+        assert_eq!(screen((6, 1), "日本\x1b[3Gx").lines(), vec!["日x"]);
+    }
+
+    #[test]
+    fn combining_characters_join_the_one_before() {
+        assert_eq!(screen((6, 1), "cafe\u{301}!").lines(), vec!["cafe\u{301}!"]);
+        assert_eq!(screen((4, 2), "abce\u{301}x").lines(), vec!["abce\u{301}", "x"]);
+        assert_eq!(screen((4, 1), "日\u{301}x").lines(), vec!["日\u{301}x"]);
+    }
+
+    #[test]
+    fn resizing_keeps_what_fits() {
+        let mut screen = screen((10, 3), "one\ntwo\nthree");
+        screen.resize((2, 2));
+        assert_eq!(screen.lines(), vec!["on", "tw"]);
+        screen.write("x");
+        assert_eq!(screen.lines(), vec!["on", "tx"]);
+    }
+}
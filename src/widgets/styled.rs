@@ -0,0 +1,453 @@
+use std::fmt::Write;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Tabs are expanded to spaces, up to the next multiple of this
+const TAB_WIDTH: usize = 8;
+
+/***
+Color: A terminal colour.
+    Indexed -> One of the 256 colours of the palette. The first 16 are the classic ones - 1 is red, 9 bright red.
+    Rgb     -> A truecolor
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/***
+Style: How some text looks. The default is plain text, in the terminal's own colours.
+ */
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub reverse: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+impl Style {
+    /// Apply the parameters of an SGR sequence (ESC [ ... m) - Ex: "1;31" for bold red. Unknown codes are ignored.
+    pub fn apply_sgr(&mut self, params: &str) {
+        let codes: Vec<u16> = params.split([';', ':']).map(|p| p.parse().unwrap_or(0)).collect();
+        let mut codes = codes.into_iter();
+
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                5 | 6 => self.blink = true,
+                7 => self.reverse = true,
+                8 => self.hidden = true,
+                9 => self.strikethrough = true,
+                21 => self.underline = true, // Double underline - as close as we get
+                22 => { self.bold = false; self.dim = false; },
+                23 => self.italic = false,
+                24 => self.underline = false,
+                25 => self.blink = false,
+                27 => self.reverse = false,
+                28 => self.hidden = false,
+                29 => self.strikethrough = false,
+                30..=37 => self.fg = Some(Color::Indexed((code - 30) as u8)),
+                38 => self.fg = extended_color(&mut codes),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Indexed((code - 40) as u8)),
+                48 => self.bg = extended_color(&mut codes),
+                49 => self.bg = None,
+                58 => { extended_color(&mut codes); }, // Underline colour - skip its arguments
+                90..=97 => self.fg = Some(Color::Indexed((code - 90 + 8) as u8)),
+                100..=107 => self.bg = Some(Color::Indexed((code - 100 + 8) as u8)),
+                _ => {}
+            }
+        }
+    }
+
+    /// The SGR sequence which sets this style from scratch. Nothing for the default style.
+    pub fn sgr(&self) -> String {
+        let mut codes: Vec<String> = vec![];
+        let attributes = [(self.bold, 1), (self.dim, 2), (self.italic, 3), (self.underline, 4), (self.blink, 5),
+                          (self.reverse, 7), (self.hidden, 8), (self.strikethrough, 9)];
+        codes.extend(attributes.iter().filter(|(set, _)| *set).map(|(_, code)| code.to_string()));
+        codes.extend(self.fg.map(|c| color_codes(c, 30)));
+        codes.extend(self.bg.map(|c| color_codes(c, 40)));
+
+        match codes.is_empty() {
+            true => String::new(),
+            false => format!("\x1b[{}m", codes.join(";"))
+        }
+    }
+}
+
+/// The colour from 38;5;n or 38;2;r;g;b, once 38 (or 48) has been read. Malformed colours reset to the default.
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    let mut byte = || codes.next().map(|c| c.min(255) as u8);
+    match byte()? {
+        5 => byte().map(Color::Indexed),
+        2 => Some(Color::Rgb(byte()?, byte()?, byte()?)),
+        _ => None
+    }
+}
+
+/// `base` is 30 for the foreground and 40 for the background.
+fn color_codes(color: Color, base: u8) -> String {
+    match color {
+        Color::Indexed(n) if n < 8 => (base + n).to_string(),
+        Color::Indexed(n) if n < 16 => (base + 60 + n - 8).to_string(),
+        Color::Indexed(n) => format!("{};5;{}", base + 8, n),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+    }
+}
+
+/***
+Span: A run of text in one style.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+/***
+StyledLine: A line of text, split into runs of the same style. Escape sequences have already been interpreted,
+    so every character in it takes up a column on screen.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StyledLine {
+    spans: Vec<Span>,
+}
+
+impl StyledLine {
+    pub fn plain(text: &str) -> StyledLine {
+        let mut line = StyledLine::default();
+        for c in text.chars() { line.push(c, Style::default()); }
+        line
+    }
+
+    /***
+    parse: Split text into lines, interpreting the SGR sequences in it. A style carries on from one line to the
+        next until it's changed, like it would on a terminal. Other escape and control sequences are dropped,
+        and tabs are expanded.
+     */
+    pub fn parse(text: &str) -> Vec<StyledLine> {
+        let mut lines = vec![StyledLine::default()];
+        let mut style = Style::default();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let line = lines.last_mut().unwrap();
+            match c {
+                '\n' => lines.push(StyledLine::default()),
+                '\t' => for _ in 0..TAB_WIDTH - line.width() % TAB_WIDTH { line.push(' ', style) },
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        let mut params = String::new();
+                        for c in chars.by_ref() {
+                            match c {
+                                '\x40'..='\x7e' => {
+                                    if c == 'm' { style.apply_sgr(&params); }
+                                    break;
+                                },
+                                _ => params.push(c)
+                            }
+                        }
+                    },
+                    Some(']') => {
+                        // Titles and the like - up to a BEL or ESC \
+                        while let Some(c) = chars.next() {
+                            if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) { break; }
+                        }
+                    },
+                    Some('(' | ')' | '*' | '+' | '#') => { chars.next(); }, // Character sets - named by the next character
+                    _ => {} // Two character sequences, which don't show anything
+                },
+                c if c.is_control() => {}, // Including the \r of \r\n
+                c => line.push(c, style),
+            }
+        }
+
+        lines
+    }
+
+    /// Add a character to the end of the line.
+    pub fn push(&mut self, c: char, style: Style) {
+        match self.spans.last_mut() {
+            Some(span) if span.style == style => span.text.push(c),
+            _ => self.spans.push(Span { text: c.to_string(), style }),
+        }
+    }
+
+    /// Add some text to the end of the line - Ex: a character and the accents combined with it.
+    pub fn push_str(&mut self, text: &str, style: Style) {
+        match self.spans.last_mut() {
+            Some(span) if span.style == style => span.text.push_str(text),
+            _ => self.spans.push(Span { text: text.to_string(), style }),
+        }
+    }
+
+    /// How many columns the line takes up.
+    pub fn width(&self) -> usize {
+        self.spans.iter().flat_map(|s| s.text.graphemes(true)).map(grapheme_width).sum()
+    }
+
+    /// Each character of the line (with any accents combined with it), and its style.
+    fn graphemes(&self) -> impl Iterator<Item = (&str, Style)> {
+        self.spans.iter().flat_map(|span| span.text.graphemes(true).map(move |g| (g, span.style)))
+    }
+
+    /// Break the line into lines no wider than `width`, wherever it reaches the edge.
+    pub fn wrap(&self, width: usize) -> Vec<StyledLine> {
+        let mut wrapped = Wrapped::new(width);
+        for (grapheme, style) in self.graphemes() { wrapped.push(grapheme, style); }
+        wrapped.lines
+    }
+
+    /***
+    wrap_words: Break the line into lines no wider than `width`, between words where possible. The spaces a line
+        is broken at are left off. Words too long to fit on a line of their own are broken wherever they reach the edge.
+     */
+    pub fn wrap_words(&self, width: usize) -> Vec<StyledLine> {
+        let mut wrapped = Wrapped::new(width);
+        let graphemes: Vec<(&str, Style)> = self.graphemes().collect();
+        let mut spaces: &[(&str, Style)] = &[];
+
+        let width_of = |graphemes: &[(&str, Style)]| graphemes.iter().map(|(g, _)| grapheme_width(g)).sum::<usize>();
+
+        for word in graphemes.chunk_by(|a, b| is_space(a.0) == is_space(b.0)) {
+            if is_space(word[0].0) {
+                match wrapped.used == 0 && wrapped.lines.len() == 1 {
+                    true => for (grapheme, style) in word { wrapped.push(grapheme, *style); }, // Indentation
+                    false => spaces = word, // Held back until we know whether the next word fits after them
+                }
+                continue;
+            }
+
+            if wrapped.used + width_of(spaces) + width_of(word) > width && wrapped.used > 0 {
+                wrapped.break_line();
+                spaces = &[];
+            }
+
+            for (grapheme, style) in spaces.iter().chain(word) { wrapped.push(grapheme, *style); }
+            spaces = &[];
+        }
+
+        wrapped.lines
+    }
+
+    /// Cut the line down to `width`, ending it with an ellipsis if anything had to go.
+    pub fn ellipsize(&self, width: usize) -> StyledLine {
+        if self.width() <= width || width == 0 { return self.clone(); }
+
+        let mut line = StyledLine::default();
+        let mut used = 0;
+        let mut style = Style::default();
+        for (grapheme, grapheme_style) in self.graphemes() {
+            if used + grapheme_width(grapheme) > width - 1 { break; }
+            used += grapheme_width(grapheme);
+            style = grapheme_style;
+            line.push_str(grapheme, style);
+        }
+
+        line.push('…', style);
+        line
+    }
+
+    /// The text of the line, without its styles.
+    #[allow(dead_code)]
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    /***
+    render: The line as exactly `width` columns of text for a VT100-ish terminal - cut short, or padded with
+        blanks. It always ends in the default style, so whatever's drawn after it doesn't pick its colours up.
+        Lines are only cut between whole characters - a wide one which doesn't fit is left off.
+     */
+    pub fn render(&self, width: usize) -> String {
+        let mut rendered = String::new();
+        let mut used = 0;
+        let mut styled = false;
+        let mut full = false;
+
+        for span in &self.spans {
+            if full { break; }
+            let mut text = String::new();
+            for grapheme in span.text.graphemes(true) {
+                full = used + grapheme_width(grapheme) > width;
+                if full { break; }
+                used += grapheme_width(grapheme);
+                text += grapheme;
+            }
+            if text.is_empty() { continue; }
+
+            let sgr = span.style.sgr();
+            if styled { rendered += "\x1b[0m"; }
+            rendered += &sgr;
+            styled = !sgr.is_empty();
+            rendered += &text;
+        }
+
+        if styled { rendered += "\x1b[0m"; }
+        write!(rendered, "{:width$}", "", width = width - used).unwrap_or(());
+        rendered
+    }
+}
+
+/***
+Wrapped: Lines being wrapped at a width, a character at a time.
+ */
+struct Wrapped {
+    lines: Vec<StyledLine>,
+    width: usize,
+    used: usize,
+}
+
+impl Wrapped {
+    fn new(width: usize) -> Wrapped {
+        Wrapped { lines: vec![StyledLine::default()], width, used: 0 }
+    }
+
+    fn break_line(&mut self) {
+        self.lines.push(StyledLine::default());
+        self.used = 0;
+    }
+
+    /// Add a character, on a new line if it doesn't fit on this one. Always at least one per line, however narrow.
+    fn push(&mut self, grapheme: &str, style: Style) {
+        if self.used + grapheme_width(grapheme) > self.width && self.used > 0 { self.break_line(); }
+        self.lines.last_mut().unwrap().push_str(grapheme, style);
+        self.used += grapheme_width(grapheme);
+    }
+}
+
+fn is_space(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+/***
+grapheme_width: How many columns a grapheme cluster takes up on screen. Wide (East Asian) characters and
+    emoji take two. Combining characters don't add to the character they're on, and terminals draw joined-up
+    emoji sequences as one, however many emoji went into them.
+ */
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().min(2)
+}
+
+/// How many columns some text takes up on screen, ignoring its escape sequences. The widest line, if there are several.
+pub fn display_width(text: &str) -> usize {
+    StyledLine::parse(text).iter().map(StyledLine::width).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn red() -> Style { Style { fg: Some(Color::Indexed(1)), ..Style::default() } }
+
+    #[test]
+    fn parses_sgr_into_spans() {
+        let lines = StyledLine::parse("plain \x1b[1;31mbold red\x1b[22m red\x1b[0m\nplain");
+        assert_eq!(lines[0].spans, vec![
+            Span { text: "plain ".to_string(), style: Style::default() },
+            Span { text: "bold red".to_string(), style: Style { bold: true, ..red() } },
+            Span { text: " red".to_string(), style: red() },
+        ]);
+        assert_eq!(lines[1], StyledLine::plain("plain"));
+    }
+
+    #[test]
+    fn styles_carry_over_to_the_next_line() {
+        let lines = StyledLine::parse("\x1b[31mone\ntwo\x1b[m");
+        assert_eq!(lines[1].spans, vec![Span { text: "two".to_string(), style: red() }]);
+    }
+
+    #[test]
+    fn understands_256_and_truecolor() {
+        let mut style = Style::default();
+        style.apply_sgr("38;5;208;48;2;10;20;30");
+        assert_eq!(style.fg, Some(Color::Indexed(208)));
+        assert_eq!(style.bg, Some(Color::Rgb(10, 20, 30)));
+        assert_eq!(style.sgr(), "\x1b[38;5;208;48;2;10;20;30m");
+
+        style.apply_sgr("39;49;94");
+        assert_eq!(style.sgr(), "\x1b[94m");
+    }
+
+    #[test]
+    fn drops_other_escape_sequences_and_control_characters() {
+        let lines = StyledLine::parse("\x1b[2J\x1b]0;title\x07a\x1b(Bb\r\n\tc");
+        assert_eq!(lines, vec![StyledLine::plain("ab"), StyledLine::plain("        c")]);
+    }
+
+    #[test]
+    fn renders_to_width_and_resets_at_the_end() {
+        let line = &StyledLine::parse("ab\x1b[31mcd\x1b[0mef")[0];
+        assert_eq!(line.render(3), "ab\x1b[31mc\x1b[0m");
+        assert_eq!(line.render(8), "ab\x1b[31mcd\x1b[0mef  ");
+        assert_eq!(line.render(2), "ab");
+        assert_eq!(StyledLine::parse("\x1b[31mred")[0].render(5), "\x1b[31mred\x1b[0m  ");
+    }
+
+    #[test]
+    fn measures_wide_and_combining_characters() {
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("cafe\u{301}"), 4);
+        assert_eq!(display_width("👍 ok"), 5);
+        assert_eq!(display_width("👨\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(display_width("┌─┐"), 3);
+    }
+
+    #[test]
+    fn renders_whole_characters_only() {
+        let line = &StyledLine::parse("日本\x1b[31m語\x1b[0mx")[0];
+        assert_eq!(line.render(3), "日 ");
+        assert_eq!(line.render(6), "日本\x1b[31m語\x1b[0m");
+        assert_eq!(line.render(7), "日本\x1b[31m語\x1b[0mx");
+        assert_eq!(StyledLine::plain("cafe\u{301}s").render(4), "cafe\u{301}");
+    }
+
+    fn texts(lines: Vec<StyledLine>) -> Vec<String> {
+        lines.iter().map(StyledLine::text).collect()
+    }
+
+    #[test]
+    fn wraps_at_the_edge() {
+        assert_eq!(texts(StyledLine::plain("abcdefgh").wrap(3)), vec!["abc", "def", "gh"]);
+        assert_eq!(texts(StyledLine::plain("日本語").wrap(3)), vec!["日", "本", "語"]);
+        assert_eq!(texts(StyledLine::plain("").wrap(3)), vec![""]);
+
+        let lines = StyledLine::parse("ab\x1b[31mcd")[0].wrap(3);
+        assert_eq!(lines[1].spans, vec![Span { text: "d".to_string(), style: red() }]);
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        assert_eq!(texts(StyledLine::plain("the quick brown fox").wrap_words(10)), vec!["the quick", "brown fox"]);
+        assert_eq!(texts(StyledLine::plain("a verylongword b").wrap_words(5)), vec!["a", "veryl", "ongwo", "rd b"]);
+        assert_eq!(texts(StyledLine::plain("  indented").wrap_words(20)), vec!["  indented"]);
+    }
+
+    #[test]
+    fn ellipsizes_what_does_not_fit() {
+        assert_eq!(StyledLine::plain("abcdef").ellipsize(4).text(), "abc…");
+        assert_eq!(StyledLine::plain("abcd").ellipsize(4).text(), "abcd");
+        assert_eq!(StyledLine::plain("日本語").ellipsize(4).text(), "日…");
+        assert_eq!(StyledLine::parse("\x1b[31mabcdef")[0].ellipsize(3).render(3), "\x1b[31mab…\x1b[0m");
+    }
+
+    #[test]
+    fn measures_text_without_its_escapes() {
+        assert_eq!(display_width("\x1b[1;31mred\x1b[0m\nlonger"), 6);
+        assert_eq!(display_width(""), 0);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::widgets::{Tabs, View, Dim, Dimensions, desired_size, content_size, display_width, CharDims, Region, Style, StyledLine};
+use std::cmp::min;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+impl Tabs {
+    pub fn new(width: Dim, height: Dim) -> Tabs {
+        Tabs {
+            pages: vec![],
+            current: 0,
+            dims: Dimensions::new(width, height),
+        }
+    }
+
+    pub fn add_page(&mut self, title: &str, child: Rc<RefCell<dyn View>>) {
+        self.pages.push((title.to_string(), child));
+    }
+
+    /// Which page is showing, counting from 0.
+    pub fn current(&self) -> usize { self.current }
+
+    /// Show the `page`th page, counting from 0 - if there's one.
+    pub fn select(&mut self, page: usize) {
+        if page < self.pages.len() { self.current = page; }
+    }
+
+    /// Show the page after this one, going back round to the first after the last.
+    pub fn next(&mut self) {
+        self.current = (self.current + 1) % self.pages.len().max(1);
+    }
+
+    /// Show the page before this one, going back round to the last before the first.
+    pub fn previous(&mut self) {
+        self.current = (self.current + self.pages.len().max(1) - 1) % self.pages.len().max(1);
+    }
+
+    /// Each page's number and title, with the one showing picked out.
+    fn bar(&self) -> StyledLine {
+        let mut bar = StyledLine::default();
+        for (i, (title, _)) in self.pages.iter().enumerate() {
+            if i > 0 { bar.push('│', Style::default()); }
+            let style = Style { reverse: i == self.current, ..Style::default() };
+            bar.push_str(&format!(" {} {} ", i + 1, title), style);
+        }
+        bar
+    }
+}
+
+impl View for Tabs {
+    fn inflate(&mut self, parent_dimensions: &CharDims) -> CharDims {
+        let room = (desired_size(&min(self.dims.width_constraint, Dim::Fixed(parent_dimensions.0))),
+                    desired_size(&min(self.dims.height_constraint, Dim::Fixed(parent_dimensions.1))));
+
+        // The bar takes the top line, and the page showing what's left
+        let page = match self.pages.get(self.current) {
+            Some((_, child)) => child.borrow_mut().inflate(&(room.0, room.1.saturating_sub(1))),
+            None => (0, 0)
+        };
+
+        self.dims.size = (content_size(self.dims.width_constraint, page.0.max(self.bar().width()), parent_dimensions.0),
+                          content_size(self.dims.height_constraint, page.1 + 1, parent_dimensions.1));
+        self.dims.size
+    }
+
+    fn constraints(&self) -> (Dim, Dim) { (self.dims.width_constraint, self.dims.height_constraint) }
+
+    fn width(&self) -> usize { self.dims.size.0 }
+
+    fn height(&self) -> usize { self.dims.size.1 }
+
+    fn render(&self) -> String {
+        self.render_lines().join("\n")
+    }
+
+    fn render_lines(&self) -> Vec<String> {
+        if self.height() == 0 { return vec![String::new()]; }
+
+        let mut lines = vec![self.bar().ellipsize(self.width()).render(self.width())];
+        if let Some((_, child)) = self.pages.get(self.current) {
+            let child = child.borrow();
+            let rows = child.render_lines().into_iter().chain(std::iter::repeat(String::new())).take(child.height());
+            // Pad by what shows on screen - lines may be full of escape sequences
+            lines.extend(rows.map(|row| format!("{}{:pad$}", row, "", pad = self.width().saturating_sub(display_width(&row)))));
+        }
+        lines.resize(self.height(), format!("{:1$}", "", self.width()));
+        lines
+    }
+
+    fn focused(&self) -> bool {
+        self.pages.get(self.current).is_some_and(|(_, child)| child.borrow().focused())
+    }
+
+    fn regions(&self) -> Vec<Region> {
+        match self.pages.get(self.current) {
+            Some((_, child)) => child.borrow().regions().into_iter().map(|r| r.offset((0, 1))).collect(),
+            None => vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::TextView;
+
+    fn tabs(pages: &[(&str, &str)]) -> Tabs {
+        let mut tabs = Tabs::new(Dim::WrapContent, Dim::WrapContent);
+        for (title, text) in pages {
+            let tv = Rc::new(RefCell::new(TextView::new(Dim::WrapContent, Dim::WrapContent)));
+            tv.borrow_mut().update_content(text.to_string());
+            tv.borrow_mut().id(title);
+            tabs.add_page(title, tv);
+        }
+        tabs
+    }
+
+    #[test]
+    fn shows_the_bar_over_the_current_page() {
+        let mut tabs = tabs(&[("cpu", "12%"), ("disk", "1.2G\nfree")]);
+        assert_eq!((16, 2), tabs.inflate(&(80, 24)));
+        assert_eq!(vec!["\x1b[7m 1 cpu \x1b[0m│ 2 disk ", "12%             "], tabs.render_lines());
+
+        tabs.next();
+        assert_eq!((16, 3), tabs.inflate(&(80, 24)));
+        assert_eq!(vec![" 1 cpu │\x1b[7m 2 disk \x1b[0m", "1.2G            ", "free            "], tabs.render_lines());
+    }
+
+    #[test]
+    fn goes_round_the_pages() {
+        let mut tabs = tabs(&[("a", ""), ("b", ""), ("c", "")]);
+        tabs.previous();
+        assert_eq!(2, tabs.current());
+        tabs.next();
+        assert_eq!(0, tabs.current());
+        tabs.select(1);
+        assert_eq!(1, tabs.current());
+        tabs.select(3);
+        assert_eq!(1, tabs.current());
+    }
+
+    #[test]
+    fn only_the_current_page_shows_up() {
+        let mut tabs = tabs(&[("a", "one"), ("b", "two")]);
+        tabs.select(1);
+        tabs.inflate(&(80, 24));
+        assert_eq!(vec![Region { id: "b".to_string(), origin: (0, 1), size: (3, 1) }], tabs.regions());
+    }
+}
@@ -1,47 +1,253 @@
-use crate::widgets::{View, TextView, Dim, Dimensions, desired_size, Vt100Formatter, CharDims};
+use crate::widgets::{View, TextView, Dim, Dimensions, desired_size, content_size, Vt100Formatter, CharDims, Screen, StyledLine, Overflow, Scroll, Region};
 use std::cmp::min;
 
 impl TextView {
     pub fn new(width: Dim, height: Dim) -> TextView {
         TextView {
+            id: None,
             raw_text: "".to_string(),
+            screen: None,
+            failure: None,
+            status: None,
+            title: None,
+            footer: None,
+            follow: false,
+            scroll: 0,
+            tail: false,
+            focused: false,
+            overflow: Overflow::default(),
             dims: Dimensions {
                 width_constraint: width,
                 height_constraint: height,
                 size: (0, 0)
             },
+            room: (0, 0),
             formatter: Box::new(Vt100Formatter{}),
             visible: true
         }
     }
 
-    pub fn update_content(&mut self, s: String) -> () {
-        self.raw_text = s;
+    /// Give the view an id to find it by in the regions of the layout it's in.
+    pub fn id(&mut self, id: &str) {
+        self.id = Some(id.to_string());
+    }
+
+    pub fn update_content(&mut self, s: String) {
+        match &mut self.screen {
+            Some(screen) => { screen.reset(); screen.write(&s); },
+            None => self.raw_text = s
+        }
+    }
+
+    /// Add a line to the end of the content, dropping the oldest once there are more than `limit`.
+    /// A terminal is written to as-is - it has no more lines than fit on its screen.
+    pub fn append_line(&mut self, line: &str, limit: usize) {
+        if let Some(screen) = &mut self.screen { return screen.write(line); }
+
+        if !self.raw_text.is_empty() { self.raw_text.push('\n'); }
+        self.raw_text += line.trim_end_matches(&['\n', '\r'][..]);
+
+        let excess = self.raw_text.split('\n').count().saturating_sub(limit);
+        if excess > 0 {
+            let cut = self.raw_text.match_indices('\n').nth(excess - 1).map_or(self.raw_text.len(), |(i, _)| i + 1);
+            self.raw_text.drain(..cut);
+        }
+    }
+
+    /// Act as a terminal, for commands which move the cursor around and redraw what they've written - Ex: `top`.
+    /// The terminal always fills the view.
+    pub fn emulate_terminal(&mut self) {
+        self.screen = Some(Screen::new(self.room));
+    }
+
+    /// Show the end of the content rather than the start, when it doesn't all fit - like `tail -f`.
+    /// Scrolling up stops it following until it's scrolled back down to the end.
+    pub fn follow(&mut self, follow: bool) {
+        self.follow = follow;
+        self.tail = follow;
+    }
+
+    /// Mark this as the view being scrolled - it shows where it's scrolled to.
+    pub fn focus(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Move through content too long to show all at once. A terminal never has more than fits, so doesn't move.
+    pub fn scroll(&mut self, scroll: Scroll) {
+        let (header, content, _) = self.arrange();
+        let room = self.height().saturating_sub(header.len());
+        let (offset, end) = self.scroll_position(content.len(), room);
+        let page = room.saturating_sub(1).max(1); // Keep a line from the last page, to read on from
+
+        self.scroll = match scroll {
+            Scroll::Up(n) => offset.saturating_sub(n),
+            Scroll::Down(n) => offset + n,
+            Scroll::PageUp => offset.saturating_sub(page),
+            Scroll::PageDown => offset + page,
+            Scroll::Top => 0,
+            Scroll::Bottom => end,
+        }.min(end);
+        self.tail = self.follow && self.scroll == end;
+    }
+
+    /// What to do with lines too long for the view.
+    pub fn overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    /// Flag this view's content as coming from a failed run. None clears the flag.
+    pub fn show_failure(&mut self, failure: Option<String>) {
+        self.failure = failure;
+    }
+
+    /// A line about the task itself, shown above its content - e.g. when it last ran. None hides it.
+    pub fn show_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    /// What the view's showing - Ex: the name of the task. A border around the view shows it. None hides it.
+    pub fn show_title(&mut self, title: Option<String>) {
+        self.title = title;
+    }
+
+    /// A line about how the content came about - Ex: when it ran, for how long. A border around the view shows it.
+    /// None hides it.
+    pub fn show_footer(&mut self, footer: Option<String>) {
+        self.footer = footer;
+    }
+
+    /// Take over another view's content - e.g. when the layout is rebuilt.
+    pub fn carry_over(&mut self, other: &TextView) {
+        self.raw_text = other.raw_text.clone();
+        if let (Some(screen), Some(old)) = (&mut self.screen, &other.screen) { *screen = old.clone(); }
+        self.failure = other.failure.clone();
+        self.status = other.status.clone();
+        self.footer = other.footer.clone();
+        self.scroll = other.scroll;
+        self.tail = self.follow && other.tail;
+    }
+
+    /// The space the content could fill, below the header - however much it fills now. Commands run in
+    /// a pty are given a terminal this size, so they lay their output out to fit.
+    pub fn room(&self) -> CharDims {
+        self.room
+    }
+
+    fn lines(&self) -> Vec<StyledLine> {
+        let mut lines = self.header_lines();
+        lines.extend(StyledLine::parse(&self.raw_text));
+        lines
+    }
+
+    /// Lines as they'll be shown at `width` - wrapped or cut short, depending on the overflow.
+    fn fit(&self, lines: Vec<StyledLine>, width: usize) -> Vec<StyledLine> {
+        match self.overflow {
+            Overflow::Truncate => lines,
+            Overflow::Wrap => lines.iter().flat_map(|l| l.wrap(width)).collect(),
+            Overflow::WrapWords => lines.iter().flat_map(|l| l.wrap_words(width)).collect(),
+            Overflow::Ellipsis => lines.iter().map(|l| l.ellipsize(width)).collect(),
+        }
+    }
+
+    fn header_lines(&self) -> Vec<StyledLine> {
+        let failure_line = self.failure.iter().map(|f| format!("!! {}", f));
+        let status_line = self.status.iter().map(|s| format!("-- {}", s));
+        failure_line.chain(status_line).map(|l| StyledLine::plain(&l)).collect()
+    }
+
+    /// The header and the content, as they'll be shown, and whether a scrollbar goes alongside the content - it
+    /// does when the view's focused and there's more than fits. A terminal shows its screen below the header,
+    /// and never has more.
+    fn arrange(&self) -> (Vec<StyledLine>, Vec<StyledLine>, bool) {
+        let header = self.fit(self.header_lines(), self.width());
+        let room = self.height().saturating_sub(header.len());
+        let content = match &self.screen {
+            Some(screen) => return (header, (0..screen.size().1).map(|row| screen.line(row)).collect(), false),
+            None => self.fit(StyledLine::parse(&self.raw_text), self.width())
+        };
+
+        // The scrollbar takes a column, which can change how the content wraps
+        match self.focused && content.len() > room && room > 0 && self.width() > 1 {
+            true => (header, self.fit(StyledLine::parse(&self.raw_text), self.width() - 1), true),
+            false => (header, content, false)
+        }
+    }
+
+    /// How many lines of `content` are scrolled past with `room` to show it in, and how many could be.
+    fn scroll_position(&self, content: usize, room: usize) -> (usize, usize) {
+        let end = content.saturating_sub(room);
+        (if self.tail { end } else { self.scroll.min(end) }, end)
+    }
+
+    /// The rows which fit in the view, each with its piece of the scrollbar if there is one.
+    /// The header stays put while the content scrolls below it.
+    fn visible_rows(&self) -> Vec<(StyledLine, Option<char>)> {
+        let (header, content, scrollbar) = self.arrange();
+        let room = self.height().saturating_sub(header.len());
+        let (offset, end) = self.scroll_position(content.len(), room);
+        let bar = if scrollbar { scrollbar_column(offset, end, room, content.len()) } else { vec![] };
+
+        let mut rows: Vec<(StyledLine, Option<char>)> = header.into_iter().map(|line| (line, None)).collect();
+        rows.extend(content.into_iter().skip(offset).take(room).enumerate().map(|(i, line)| (line, bar.get(i).copied())));
+        rows.truncate(self.height());
+        rows
     }
 }
 
+/// A scrollbar `room` rows tall, for `total` lines of content scrolled `offset` of the way to `end`. The thumb is
+/// sized to the part in view, and only touches either end of the track when the content's scrolled right to it.
+fn scrollbar_column(offset: usize, end: usize, room: usize, total: usize) -> Vec<char> {
+    let thumb = (room * room / total).clamp(1, room);
+    let travel = room - thumb;
+    let start = match offset {
+        0 => 0,
+        o if o >= end => travel,
+        o => ((o * travel + end / 2) / end).clamp(1.min(travel), travel.saturating_sub(1).max(1.min(travel)))
+    };
+
+    (0..room).map(|row| if row >= start && row < start + thumb { '█' } else { '│' }).collect()
+}
+
 impl View for TextView {
     fn inflate(&mut self, parent_dimensions: &CharDims) -> CharDims {
         if !self.visible {
             self.dims.size = (0, 0);
+            self.room = (0, 0);
             return self.dims.size;
         }
 
-        let text_size = self.raw_text.split("\n").map(|c| c.len()).max().unwrap();
-        let desired_width_constraint = Dim::UpTo(text_size);
-        let desired_height_constraint  = Dim::UpTo(self.raw_text.split("\n").count());
+        let available_width = min(self.dims.width_constraint, Dim::Fixed(parent_dimensions.0));
+        let available_height = min(self.dims.height_constraint, Dim::Fixed(parent_dimensions.1));
+        self.room = (desired_size(&available_width), desired_size(&available_height).saturating_sub(self.header_lines().len()));
 
-        let most_restrictive_width = min(desired_width_constraint, min(self.dims.width_constraint, Dim::Fixed(parent_dimensions.0)));
-        let most_restrictive_height= min(desired_height_constraint,  min(self.dims.height_constraint, Dim::Fixed(parent_dimensions.1)));
+        if let Some(screen) = &mut self.screen {
+            if screen.size() != self.room { screen.resize(self.room); }
+            self.dims.size = (self.room.0, min(desired_size(&available_height), self.header_lines().len() + self.room.1));
+            return self.dims.size;
+        }
 
-        self.dims.size = (desired_size(&most_restrictive_width),
-                          desired_size(&most_restrictive_height));
+        let lines = self.lines();
+        let text_size = lines.iter().map(StyledLine::width).max().unwrap();
+        let width = content_size(self.dims.width_constraint, text_size, parent_dimensions.0);
+
+        // Wrapped lines take up more of them, so we need the width before we know the height
+        let height = content_size(self.dims.height_constraint, self.fit(lines, width).len(), parent_dimensions.1);
+
+        self.dims.size = (width, height);
+
+        // The scrollbar of a focused view takes a column. Keep one spare for it whether focused or not, if there's
+        // room, so moving the focus around doesn't shift the layout.
+        let header = self.fit(self.header_lines(), self.width()).len();
+        let content = self.fit(StyledLine::parse(&self.raw_text), self.width()).len();
+        if self.height() > header && content > self.height() - header {
+            self.dims.size.0 = content_size(self.dims.width_constraint, text_size + 1, parent_dimensions.0);
+        }
 
-        self.dims.size.clone()
+        self.dims.size
     }
 
     fn constraints(&self) -> (Dim, Dim) {
-        (self.dims.width_constraint.clone(), self.dims.height_constraint.clone())
+        (self.dims.width_constraint, self.dims.height_constraint)
     }
 
     fn width(&self) -> usize { self.dims.size.0 }
@@ -49,9 +255,12 @@ impl View for TextView {
     fn height(&self) -> usize { self.dims.size.1 }
 
     fn render(&self) -> String {
-        self.raw_text.
-            split("\n").take(self.height()). // First n Lines
-            map(|c| self.formatter.format(c.to_string(), self.width())). // Format them
+        self.visible_rows().
+            iter(). // The lines which fit
+            map(|(line, bar)| match bar { // Format them, leaving a column for the scrollbar
+                Some(bar) => format!("{}{}", self.formatter.format(line, self.width() - 1), bar),
+                None => self.formatter.format(line, self.width())
+            }).
             collect::<Vec<String>>().join("\n")     // Convert back into a single string
     }
 
@@ -61,6 +270,19 @@ impl View for TextView {
             .map(|s| s.to_string())
             .collect()
     }
+
+    fn focused(&self) -> bool { self.focused }
+
+    fn title(&self) -> Option<String> { self.title.clone() }
+
+    fn footer(&self) -> Option<String> { self.footer.clone() }
+
+    fn regions(&self) -> Vec<Region> {
+        match &self.id {
+            Some(id) if self.width() > 0 && self.height() > 0 => vec![Region { id: id.clone(), origin: (0, 0), size: self.dims.size }],
+            _ => vec![]
+        }
+    }
 }
 
 #[cfg(test)]
@@ -116,6 +338,38 @@ mod tests {
         assert_eq!(2, tw.height());
     }
 
+    #[test]
+    fn inflation_measures_what_shows_on_screen() {
+        let mut tw = wrap_content_text_widget();
+        tw.raw_text = String::from("\x1b[1m日本語\x1b[0m\nre\u{301}sume\u{301}");
+        tw.inflate(&(100, 100));
+        assert_eq!((6, 2), (tw.width(), tw.height()));
+        assert_eq!(vec!["\x1b[1m日本語\x1b[0m", "re\u{301}sume\u{301}"], tw.render_lines());
+    }
+
+    #[test]
+    fn truncates_between_characters() {
+        let mut tw = TextView::new(Dim::Fixed(5), Dim::WrapContent);
+        tw.raw_text = String::from("日本語\n┌───────┐\nnaïve café");
+        tw.inflate(&(100, 100));
+        assert_eq!(vec!["日本 ", "┌────", "naïve"], tw.render_lines());
+    }
+
+    #[test]
+    fn between_keeps_the_size_within_bounds() {
+        let mut tw = TextView::new(Dim::Between(5, 8), Dim::Between(2, 3));
+        tw.raw_text = String::from("ab");
+        tw.inflate(&(100, 100));
+        assert_eq!((5, 2), (tw.width(), tw.height()));
+
+        tw.raw_text = String::from("a long line\n2\n3\n4");
+        tw.inflate(&(100, 100));
+        assert_eq!((8, 3), (tw.width(), tw.height()));
+
+        tw.inflate(&(4, 1));
+        assert_eq!((4, 1), (tw.width(), tw.height()));
+    }
+
     #[test]
     fn renders_all_text_within_wrap_content() {
         let mut tw = wrap_content_text_widget();
@@ -132,6 +386,174 @@ mod tests {
         assert_eq!(String::from("some reall\nand anothe"), tw.render());
     }
 
+    #[test]
+    fn failures_are_flagged_above_the_content() {
+        let mut tw = wrap_content_text_widget();
+        tw.raw_text = String::from("some\ntext");
+        tw.show_failure(Some(String::from("exited with 1")));
+        tw.inflate(&(100, 100));
+        assert_eq!(String::from("!! exited with 1\nsome            \ntext            "), tw.render());
+
+        tw.show_failure(None);
+        tw.inflate(&(100, 100));
+        assert_eq!(String::from("some\ntext"), tw.render());
+    }
+
+    #[test]
+    fn status_goes_below_failures_and_above_the_content() {
+        let mut tw = wrap_content_text_widget();
+        tw.raw_text = String::from("done");
+        tw.show_status(Some(String::from("last run at 12:00:00")));
+        tw.show_failure(Some(String::from("exited with 1")));
+        tw.inflate(&(100, 100));
+        assert_eq!(vec!["!! exited with 1       ", "-- last run at 12:00:00", "done                   "], tw.render_lines());
+    }
+
+    #[test]
+    fn appended_lines_are_limited_to_the_scrollback() {
+        let mut tw = wrap_content_text_widget();
+        for line in &["one\n", "two\r\n", "three\n", "four\n"] {
+            tw.append_line(line, 3);
+        }
+        assert_eq!(tw.raw_text, "two\nthree\nfour");
+
+        tw.append_line("five", 1);
+        assert_eq!(tw.raw_text, "five");
+    }
+
+    #[test]
+    fn following_shows_the_end_of_the_content() {
+        let mut tw = fixed_size_text_widget();
+        tw.raw_text = String::from("one\ntwo\nthree");
+        tw.follow(true);
+        tw.inflate(&(100, 100));
+        assert_eq!(String::from("two   \nthree "), tw.render());
+
+        tw.show_failure(Some(String::from("oops")));
+        tw.inflate(&(100, 100));
+        assert_eq!(String::from("!! oops \nthree   "), tw.render());
+    }
+
+    #[test]
+    fn scrolling_moves_through_content_too_long_to_show() {
+        let mut tw = TextView::new(Dim::Fixed(6), Dim::Fixed(2));
+        tw.raw_text = String::from("line 1\nline 2\nline 3\nline 4\nline 5");
+        tw.inflate(&(100, 100));
+
+        tw.scroll(Scroll::Down(1));
+        assert_eq!(vec!["line 2", "line 3"], tw.render_lines());
+        tw.scroll(Scroll::PageDown);
+        assert_eq!(vec!["line 3", "line 4"], tw.render_lines());
+        tw.scroll(Scroll::Down(5));
+        assert_eq!(vec!["line 4", "line 5"], tw.render_lines());
+        tw.scroll(Scroll::Top);
+        tw.scroll(Scroll::PageUp);
+        assert_eq!(vec!["line 1", "line 2"], tw.render_lines());
+        tw.scroll(Scroll::Bottom);
+        assert_eq!(vec!["line 4", "line 5"], tw.render_lines());
+    }
+
+    #[test]
+    fn scrolling_up_stops_following_until_back_at_the_end() {
+        let mut tw = TextView::new(Dim::Fixed(6), Dim::Fixed(2));
+        tw.raw_text = String::from("line 1\nline 2\nline 3");
+        tw.follow(true);
+        tw.inflate(&(100, 100));
+
+        tw.scroll(Scroll::Up(1));
+        tw.append_line("line 4", 100);
+        assert_eq!(vec!["line 1", "line 2"], tw.render_lines());
+
+        tw.scroll(Scroll::Bottom);
+        tw.append_line("line 5", 100);
+        assert_eq!(vec!["line 4", "line 5"], tw.render_lines());
+    }
+
+    #[test]
+    fn focused_views_show_where_they_are_scrolled_to() {
+        let mut tw = TextView::new(Dim::Fixed(5), Dim::Fixed(3));
+        tw.raw_text = String::from("one\ntwo\nthree\nfour\nfive\nsix");
+        tw.inflate(&(100, 100));
+        assert_eq!(vec!["one  ", "two  ", "three"], tw.render_lines());
+
+        tw.focus(true);
+        assert_eq!(vec!["one █", "two │", "thre│"], tw.render_lines());
+        tw.scroll(Scroll::Down(1));
+        assert_eq!(vec!["two │", "thre█", "four│"], tw.render_lines());
+        tw.scroll(Scroll::Bottom);
+        assert_eq!(vec!["four│", "five│", "six █"], tw.render_lines());
+    }
+
+    #[test]
+    fn focused_views_make_room_for_their_scrollbar() {
+        let mut tw = TextView::new(Dim::Fixed(10), Dim::Fixed(2));
+        tw.raw_text = String::from("9\n10\n11");
+        tw.focus(true);
+        tw.inflate(&(100, 100));
+        assert_eq!(vec!["9 █", "10│"], tw.render_lines());
+    }
+
+    #[test]
+    fn room_is_the_space_below_the_header_whatever_the_content() {
+        let mut tw = TextView::new(Dim::Fixed(40), Dim::WrapContent);
+        tw.raw_text = String::from("short");
+        tw.show_status(Some(String::from("running")));
+        tw.inflate(&(100, 20));
+        assert_eq!((40, 19), tw.room());
+
+        tw.inflate(&(30, 10));
+        assert_eq!((30, 9), tw.room());
+    }
+
+    #[test]
+    fn terminals_fill_the_view_below_the_header() {
+        let mut tw = TextView::new(Dim::Fixed(6), Dim::WrapContent);
+        tw.emulate_terminal();
+        tw.show_status(Some(String::from("top")));
+        tw.inflate(&(100, 3));
+        tw.append_line("50%\r", 10);
+        tw.append_line("done\n", 10);
+        assert_eq!((6, 3), (tw.width(), tw.height()));
+        assert_eq!(vec!["-- top", "done  ", "      "], tw.render_lines());
+
+        tw.update_content(String::from("\x1b[5Bbottom"));
+        tw.inflate(&(100, 4));
+        assert_eq!(vec!["-- top", "      ", "bottom", "      "], tw.render_lines());
+    }
+
+    #[test]
+    fn wrapping_grows_wrap_content_views() {
+        let mut tw = TextView::new(Dim::Fixed(10), Dim::WrapContent);
+        tw.raw_text = String::from("a long line of text\nshort");
+        tw.overflow(Overflow::WrapWords);
+        tw.inflate(&(100, 100));
+        assert_eq!(4, tw.height());
+        assert_eq!(vec!["a long    ", "line of   ", "text      ", "short     "], tw.render_lines());
+
+        tw.overflow(Overflow::Wrap);
+        tw.inflate(&(100, 100));
+        assert_eq!(vec!["a long lin", "e of text ", "short     "], tw.render_lines());
+    }
+
+    #[test]
+    fn wrapped_views_follow_the_last_wrapped_line() {
+        let mut tw = TextView::new(Dim::Fixed(4), Dim::Fixed(2));
+        tw.raw_text = String::from("one\ntwo three");
+        tw.overflow(Overflow::Wrap);
+        tw.follow(true);
+        tw.inflate(&(100, 100));
+        assert_eq!(vec!["thre", "e   "], tw.render_lines());
+    }
+
+    #[test]
+    fn ellipsis_marks_cut_lines() {
+        let mut tw = TextView::new(Dim::Fixed(5), Dim::WrapContent);
+        tw.raw_text = String::from("truncated\nfits");
+        tw.overflow(Overflow::Ellipsis);
+        tw.inflate(&(100, 100));
+        assert_eq!(vec!["trun…", "fits "], tw.render_lines());
+    }
+
     #[test]
     fn when_invisible_renders_nothing() {
         let mut tw = fixed_size_text_widget();